@@ -1,8 +1,14 @@
 use crate::args::Command;
+use crate::components::ac::Ac;
 use crate::components::microphone::MicrophoneOp;
-use crate::components::{brightness::BrightnessOp, system::SystemOp, volume::VolumeOp};
+use crate::components::{
+    brightness::BrightnessOp,
+    system::{Schedule, SystemOp},
+    volume::VolumeOp,
+};
 use crate::config::Config;
 use crate::manager::Manager;
+use anyhow::anyhow;
 use std::io::{BufRead, BufReader};
 use std::os::unix::net::UnixStream;
 use std::time::{Duration, Instant};
@@ -17,15 +23,22 @@ impl Acpi {
 
         let delay = Duration::from_micros(100);
         let mut last = Instant::now();
-        for line in reader.lines().flatten() {
-            if last.elapsed() >= delay {
-                let event = line.split(' ').collect::<Vec<&str>>();
-                Self::handle(&event, config)?;
-                last = Instant::now();
+        // events are handled aside, so that a key pressed during a fade retargets it
+        std::thread::scope(|scope| {
+            for line in reader.lines().map_while(Result::ok) {
+                if last.elapsed() >= delay {
+                    scope.spawn(move || {
+                        let event = line.split(' ').collect::<Vec<&str>>();
+                        // a failing handler must not stop lid and sleep events from being handled
+                        if let Err(e) = Self::handle(&event, config) {
+                            eprintln!("Could not handle ACPI event '{line}': {e}");
+                        }
+                    });
+                    last = Instant::now();
+                }
             }
-        }
-
-        Ok(())
+            Err(anyhow!("Connection to acpid closed"))
+        })
     }
 
     fn handle(event: &[&str], config: &Config) -> anyhow::Result<()> {
@@ -33,17 +46,23 @@ impl Acpi {
         match event.get(0) {
             Some(&"button/lid") => match event.get(2) {
                 Some(&"close") => Some(Command::System {
-                    operation: SystemOp::Suspend,
+                    operation: SystemOp::Suspend {
+                        schedule: Schedule::default(),
+                    },
                 }),
                 _ => None,
             },
             Some(&"button/sleep") => Some(Command::System {
-                operation: SystemOp::Suspend,
+                operation: SystemOp::Suspend {
+                    schedule: Schedule::default(),
+                },
             }),
             Some(&"video/brightnessup") => Some(Command::Brightness {
+                device: None,
                 operation: BrightnessOp::Up { percent: 5 },
             }),
             Some(&"video/brightnessdown") => Some(Command::Brightness {
+                device: None,
                 operation: BrightnessOp::Down { percent: 5 },
             }),
             Some(&"button/volumeup") => Some(Command::Volume {
@@ -55,14 +74,13 @@ impl Acpi {
             Some(&"button/mute") => Some(Command::Volume {
                 operation: VolumeOp::Mute,
             }),
-            Some(&"jack/headphone") => Some(Command::Volume {
-                operation: VolumeOp::Update,
-            }),
+            // like `ac_adapter ACPI0003:00 00000080 00000001`, sysfs tells which adapter is online
+            Some(&"ac_adapter") => return Ac::update(config),
             Some(&"button/f20") => Some(Command::Microphone {
                 operation: MicrophoneOp::Mute,
             }),
             _ => None,
         }
-        .map_or(Ok(()), |e| Manager::handle(e, config))
+        .map_or(Ok(()), |e| Manager::handle(e, config).map(|_| ()))
     }
 }
@@ -1,8 +1,10 @@
 use crate::components::{
-    brightness::BrightnessOp, layout::LayoutOp, microphone::MicrophoneOp, system::SystemOp,
-    volume::VolumeOp, wifi::WifiTurnType,
+    battery::BatteryOp, brightness::BrightnessOp, layout::LayoutOp, microphone::MicrophoneOp,
+    power_profile::PowerProfileOp, system::SystemOp, volume::VolumeOp, wifi::WifiTurnType,
 };
-use clap::{command, Parser, Subcommand};
+use crate::daemon::DaemonOp;
+use clap::{Parser, Subcommand};
+use serde::{Deserialize, Serialize};
 
 #[derive(Parser)]
 #[command(author, version, about)]
@@ -11,10 +13,16 @@ pub struct Args {
     pub command: Command,
 }
 
-#[derive(Subcommand)]
+#[derive(Clone, Subcommand, Serialize, Deserialize)]
 pub enum Command {
     /// Launch manager daemon
-    Daemon,
+    Daemon {
+        /// Stop the running daemon and take its place
+        #[arg(long)]
+        replace: bool,
+        #[command(subcommand)]
+        operation: Option<DaemonOp>,
+    },
     /// Commands to manage systemd
     System {
         #[command(subcommand)]
@@ -22,9 +30,22 @@ pub enum Command {
     },
     /// Commands to manage backlight
     Brightness {
+        /// Id, name or kind (display, keyboard, external) of the device
+        #[arg(long)]
+        device: Option<String>,
         #[command(subcommand)]
         operation: BrightnessOp,
     },
+    /// Commands to manage batteries
+    Battery {
+        #[command(subcommand)]
+        operation: BatteryOp,
+    },
+    /// Commands to manage power profiles
+    PowerProfile {
+        #[command(subcommand)]
+        operation: PowerProfileOp,
+    },
     /// Commands to manage volume
     Volume {
         #[command(subcommand)]
@@ -46,3 +67,16 @@ pub enum Command {
         operation: MicrophoneOp,
     },
 }
+
+impl Command {
+    /// Whether the command can be handled by the running daemon instead of this process
+    pub fn routable(&self) -> bool {
+        !matches!(
+            self,
+            Command::Daemon {
+                operation: None,
+                ..
+            }
+        )
+    }
+}
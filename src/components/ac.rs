@@ -0,0 +1,166 @@
+use std::path::Path;
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+
+use crate::config::Config;
+
+use super::brightness::Brightness;
+use super::components::ScaledComponent;
+use super::power_profile::{PowerProfile, Profile};
+
+/// Last known AC state, with the settings to restore once back on AC
+static STATE: Mutex<Option<(bool, PowerSettings)>> = Mutex::new(None);
+
+/// Settings applied when the computer switches power source, unset ones are left alone
+#[derive(Deserialize, Serialize, Clone, Default, Debug)]
+#[serde(default)]
+pub struct PowerSettings {
+    /// Perceived brightness percentage of the display
+    pub brightness: Option<u32>,
+    pub power_profile: Option<Profile>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Default, Debug)]
+#[serde(default)]
+pub struct AcConfig {
+    /// Applied when plugged in, settings changed on battery are restored unless set here
+    pub on_ac: PowerSettings,
+    pub on_battery: PowerSettings,
+}
+
+/// Power adapters of the computer
+pub struct Ac;
+
+impl Ac {
+    /// Whether an adapter is online under the sysfs mounted at `root`, `None` if there is none
+    pub fn online(root: &Path) -> anyhow::Result<Option<bool>> {
+        let supplies = root.join("class/power_supply");
+        let mut online = None;
+        if !supplies.exists() {
+            return Ok(online);
+        }
+        for entry in std::fs::read_dir(supplies)? {
+            let path = entry?.path();
+            let kind = std::fs::read_to_string(path.join("type")).unwrap_or_default();
+            if kind.trim() != "Mains" {
+                continue;
+            }
+            let value = std::fs::read_to_string(path.join("online"))?;
+            online = Some(online.unwrap_or(false) || value.trim() == "1");
+        }
+        Ok(online)
+    }
+
+    /// Remembers the current AC state, so that only later transitions apply settings
+    pub fn init(config: &Config) -> anyhow::Result<()> {
+        if let Some(online) = Self::online(&config.sysfs_root)? {
+            *STATE.lock().unwrap() = Some((online, PowerSettings::default()));
+        }
+        Ok(())
+    }
+
+    /// Applies the settings of the current power source if it changed since the last call
+    pub fn update(config: &Config) -> anyhow::Result<()> {
+        let Some(online) = Self::online(&config.sysfs_root)? else {
+            return Ok(());
+        };
+        let mut state = STATE.lock().unwrap();
+        if state.as_ref().is_some_and(|(last, _)| *last == online) {
+            return Ok(());
+        }
+        let saved = state.as_ref().map(|(_, saved)| saved.clone());
+        let (settings, restore, saved) = if online {
+            (
+                &config.ac.on_ac,
+                saved.unwrap_or_default(),
+                PowerSettings::default(),
+            )
+        } else {
+            let previous = Self::current(config, &config.ac.on_battery);
+            (&config.ac.on_battery, PowerSettings::default(), previous)
+        };
+        *state = Some((online, saved));
+        Self::apply(config, settings, restore);
+        Ok(())
+    }
+
+    /// Returns the current values of the settings that `settings` would change
+    fn current(config: &Config, settings: &PowerSettings) -> PowerSettings {
+        PowerSettings {
+            brightness: settings
+                .brightness
+                .and_then(|_| Brightness::new(config, None).and_then(|b| b.get()).ok()),
+            power_profile: settings
+                .power_profile
+                .and_then(|_| PowerProfile::new().and_then(|p| p.get()).ok()),
+        }
+    }
+
+    /// Applies `settings`, falling back to `restore` for the unset ones.
+    /// Settings are independent, so one failing does not prevent the others.
+    fn apply(config: &Config, settings: &PowerSettings, restore: PowerSettings) {
+        if let Some(percent) = settings.brightness.or(restore.brightness) {
+            if let Err(e) = Brightness::new(config, None).and_then(|b| b.set(percent)) {
+                eprintln!("Could not set brightness to {percent}%: {e}");
+            }
+        }
+        if let Some(profile) = settings.power_profile.or(restore.power_profile) {
+            if let Err(e) = PowerProfile::new().and_then(|p| p.set(profile)) {
+                eprintln!("Could not switch to {profile}: {e}");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fake_sysfs::FakeSysfs;
+
+    /// Builds a fake sysfs with power supplies given as their type and online state
+    fn with_supplies(name: &str, supplies: &[(&str, &str, u32)]) -> FakeSysfs {
+        let mut sysfs = FakeSysfs::new(&format!("ac-{name}")).dir("class/power_supply");
+        for (supply, kind, online) in supplies {
+            sysfs = sysfs
+                .file(&format!("class/power_supply/{supply}/type"), kind)
+                .file(&format!("class/power_supply/{supply}/online"), online);
+        }
+        sysfs
+    }
+
+    #[test]
+    fn online_if_any_adapter_is() {
+        let sysfs = with_supplies(
+            "any",
+            &[
+                ("AC", "Mains", 0),
+                ("ADP1", "Mains", 1),
+                ("BAT0", "Battery", 0),
+            ],
+        );
+        assert_eq!(Ac::online(sysfs.root()).unwrap(), Some(true));
+    }
+
+    #[test]
+    fn offline_when_all_adapters_are() {
+        let sysfs = with_supplies("offline", &[("AC", "Mains", 0), ("BAT0", "Battery", 1)]);
+        assert_eq!(Ac::online(sysfs.root()).unwrap(), Some(false));
+    }
+
+    #[test]
+    fn none_without_adapter() {
+        let sysfs = with_supplies(
+            "none",
+            &[("BAT0", "Battery", 1), ("hid-mouse", "Battery", 1)],
+        );
+        assert_eq!(Ac::online(sysfs.root()).unwrap(), None);
+        assert_eq!(Ac::online(&sysfs.path("missing")).unwrap(), None);
+    }
+
+    #[test]
+    fn unreadable_adapter_is_an_error() {
+        let sysfs = with_supplies("unreadable", &[]).file("class/power_supply/AC/type", "Mains");
+        assert!(Ac::online(sysfs.root()).is_err());
+    }
+}
@@ -0,0 +1,152 @@
+pub mod pulse;
+pub mod wpctl;
+
+use std::fmt::Display;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use self::{pulse::Pulse, wpctl::Wpctl};
+
+/// Volume (in percent) and mute state of an audio device
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct AudioLevel {
+    pub volume: u32,
+    pub muted: bool,
+}
+
+/// Kind of audio device
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum DeviceKind {
+    Sink,
+    Source,
+}
+
+/// What changed on the audio server
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum AudioEvent {
+    Sink,
+    Source,
+    /// Playback streams of applications
+    Stream,
+}
+
+/// An output (sink) or input (source) known by the audio server
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct AudioDevice {
+    pub id: u32,
+    pub name: String,
+    pub description: String,
+    pub default: bool,
+}
+
+impl AudioDevice {
+    /// Finds the device whose id, name or description is `query`
+    pub fn find(devices: Vec<Self>, query: &str) -> anyhow::Result<Self> {
+        let id = query.parse::<u32>().ok();
+        devices
+            .into_iter()
+            .find(|d| Some(d.id) == id || d.name == query || d.description == query)
+            .ok_or(anyhow::anyhow!("Could not find device '{query}'"))
+    }
+
+    /// Formats `devices` one per line, marking the default one
+    pub fn list(devices: &[Self]) -> String {
+        devices
+            .iter()
+            .map(Self::to_string)
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+}
+
+impl Display for AudioDevice {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let default = if self.default { "*" } else { " " };
+        write!(f, "{default} {:>4}. {}", self.id, self.description)?;
+        if self.name != self.description {
+            write!(f, " ({})", self.name)?;
+        }
+        Ok(())
+    }
+}
+
+/// Sound played by an application
+#[derive(Clone, PartialEq, Debug)]
+pub struct AudioStream {
+    pub id: u32,
+    pub app: String,
+    pub level: AudioLevel,
+}
+
+impl AudioStream {
+    /// Finds the streams whose id or application name is `query`, ignoring case
+    pub fn find(streams: Vec<Self>, query: &str) -> anyhow::Result<Vec<Self>> {
+        let id = query.parse::<u32>().ok();
+        let streams = streams
+            .into_iter()
+            .filter(|s| Some(s.id) == id || s.app.eq_ignore_ascii_case(query))
+            .collect::<Vec<Self>>();
+        if streams.is_empty() {
+            Err(anyhow::anyhow!("Could not find application '{query}'"))
+        } else {
+            Ok(streams)
+        }
+    }
+}
+
+impl Display for AudioStream {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:>4}. {} {}%", self.id, self.app, self.level.volume)?;
+        if self.level.muted {
+            write!(f, " [muted]")?;
+        }
+        Ok(())
+    }
+}
+
+/// A way of talking to the audio server
+pub trait AudioBackend {
+    /// Returns the level of the default device of `kind`, `None` if there is none
+    fn get(&self, kind: DeviceKind) -> anyhow::Result<Option<AudioLevel>>;
+    fn set_volume(&self, kind: DeviceKind, percent: u32) -> anyhow::Result<()>;
+    fn set_mute(&self, kind: DeviceKind, muted: bool) -> anyhow::Result<()>;
+    fn devices(&self, kind: DeviceKind) -> anyhow::Result<Vec<AudioDevice>>;
+    fn set_default(&self, kind: DeviceKind, device: &AudioDevice) -> anyhow::Result<()>;
+    fn streams(&self) -> anyhow::Result<Vec<AudioStream>>;
+    fn set_stream_volume(&self, stream: &AudioStream, percent: u32) -> anyhow::Result<()>;
+    fn set_stream_mute(&self, stream: &AudioStream, muted: bool) -> anyhow::Result<()>;
+
+    /// Calls `on_change` every time something may have changed on the audio server, never returns
+    /// unless an error occurs.
+    /// Backends without change events poll, leaving it to `on_change` to find out what changed.
+    fn subscribe(
+        &self,
+        on_change: &mut dyn FnMut(AudioEvent) -> anyhow::Result<()>,
+    ) -> anyhow::Result<()> {
+        loop {
+            on_change(AudioEvent::Sink)?;
+            on_change(AudioEvent::Source)?;
+            on_change(AudioEvent::Stream)?;
+            std::thread::sleep(Duration::from_secs(1));
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, Default)]
+pub enum AudioBackendType {
+    /// Parse the output of `wpctl`, polling for changes as it has no change events
+    Wpctl,
+    /// Talk to the PulseAudio compatible socket of PipeWire directly, subscribing to its events
+    #[default]
+    Native,
+}
+
+impl AudioBackendType {
+    pub fn backend(&self) -> anyhow::Result<Box<dyn AudioBackend>> {
+        Ok(match self {
+            AudioBackendType::Wpctl => Box::new(Wpctl),
+            AudioBackendType::Native => Box::new(Pulse::connect()?),
+        })
+    }
+}
@@ -0,0 +1,277 @@
+use std::cell::{Cell, RefCell};
+use std::ffi::{CStr, CString};
+use std::io::BufReader;
+use std::os::unix::net::UnixStream;
+
+use anyhow::anyhow;
+use pulseaudio::protocol::{
+    self, ChannelVolume, Command, CommandReply, ProtocolError, Volume as RawVolume,
+};
+
+use super::{AudioBackend, AudioDevice, AudioEvent, AudioLevel, AudioStream, DeviceKind};
+
+const CLIENT_NAME: &str = "emanager";
+
+/// Client of the PulseAudio protocol, which PipeWire serves through `pipewire-pulse`
+pub struct Pulse {
+    socket: RefCell<BufReader<UnixStream>>,
+    seq: Cell<u32>,
+    version: u16,
+}
+
+impl Pulse {
+    pub fn connect() -> anyhow::Result<Self> {
+        let path = pulseaudio::socket_path_from_env()
+            .ok_or(anyhow!("Could not find the PulseAudio socket"))?;
+        let mut pulse = Self {
+            socket: RefCell::new(BufReader::new(UnixStream::connect(path)?)),
+            seq: Cell::new(0),
+            version: protocol::MAX_VERSION,
+        };
+
+        let cookie = pulseaudio::cookie_path_from_env()
+            .and_then(|path| std::fs::read(path).ok())
+            .unwrap_or_default();
+        let auth = pulse.request::<protocol::AuthReply>(&Command::Auth(protocol::AuthParams {
+            version: protocol::MAX_VERSION,
+            supports_shm: false,
+            supports_memfd: false,
+            cookie,
+        }))?;
+        pulse.version = pulse.version.min(auth.version);
+
+        let mut props = protocol::Props::new();
+        props.set(protocol::Prop::ApplicationName, CString::new(CLIENT_NAME)?);
+        pulse.request::<protocol::SetClientNameReply>(&Command::SetClientName(props))?;
+        Ok(pulse)
+    }
+
+    fn request<R: CommandReply>(&self, command: &Command) -> Result<R, ProtocolError> {
+        let mut socket = self.socket.borrow_mut();
+        self.write(&mut socket, command)?;
+        let (_, reply) = protocol::read_reply_message(&mut *socket, self.version)?;
+        Ok(reply)
+    }
+
+    fn ack(&self, command: &Command) -> Result<(), ProtocolError> {
+        let mut socket = self.socket.borrow_mut();
+        self.write(&mut socket, command)?;
+        protocol::read_ack_message(&mut *socket)?;
+        Ok(())
+    }
+
+    fn write(
+        &self,
+        socket: &mut BufReader<UnixStream>,
+        command: &Command,
+    ) -> Result<(), ProtocolError> {
+        let seq = self.seq.get();
+        self.seq.set(seq.wrapping_add(1));
+        protocol::write_command_message(socket.get_mut(), seq, command, self.version)
+    }
+
+    /// Returns the index, volume and mute state of the default device of `kind`, `None` if the
+    /// server has none
+    fn default_device(
+        &self,
+        kind: DeviceKind,
+    ) -> anyhow::Result<Option<(u32, ChannelVolume, bool)>> {
+        let device = match kind {
+            DeviceKind::Sink => self
+                .request::<protocol::SinkInfo>(&Command::GetSinkInfo(protocol::GetSinkInfo {
+                    index: None,
+                    name: Some(protocol::DEFAULT_SINK.to_owned()),
+                }))
+                .map(|info| (info.index, info.cvolume, info.muted)),
+            DeviceKind::Source => self
+                .request::<protocol::SourceInfo>(&Command::GetSourceInfo(protocol::GetSourceInfo {
+                    index: None,
+                    name: Some(protocol::DEFAULT_SOURCE.to_owned()),
+                }))
+                .map(|info| (info.index, info.cvolume, info.muted)),
+        };
+        match device {
+            Ok(device) => Ok(Some(device)),
+            Err(ProtocolError::ServerError(_)) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn device(index: u32, name: &CStr, description: Option<&CStr>, default: &CStr) -> AudioDevice {
+        let name = name.to_string_lossy().to_string();
+        AudioDevice {
+            id: index,
+            description: description.map_or(name.clone(), |d| d.to_string_lossy().to_string()),
+            default: name.as_bytes() == default.to_bytes(),
+            name,
+        }
+    }
+
+    fn to_percent(volume: &ChannelVolume) -> u32 {
+        let channels = volume.channels();
+        if channels.is_empty() {
+            return 0;
+        }
+        let raw = channels.iter().map(|v| v.as_u32() as f32).sum::<f32>() / channels.len() as f32;
+        (raw * 100. / RawVolume::NORM.as_u32() as f32).round() as u32
+    }
+
+    fn from_percent(percent: u32, channels: usize) -> ChannelVolume {
+        let raw = RawVolume::from_u32_clamped(RawVolume::NORM.as_u32() * percent / 100);
+        let mut volume = ChannelVolume::empty();
+        for _ in 0..channels.max(1) {
+            volume.push(raw);
+        }
+        volume
+    }
+}
+
+impl AudioBackend for Pulse {
+    fn get(&self, kind: DeviceKind) -> anyhow::Result<Option<AudioLevel>> {
+        Ok(self
+            .default_device(kind)?
+            .map(|(_, volume, muted)| AudioLevel {
+                volume: Self::to_percent(&volume),
+                muted,
+            }))
+    }
+
+    fn set_volume(&self, kind: DeviceKind, percent: u32) -> anyhow::Result<()> {
+        let (index, volume, _) = self.default_device(kind)?.ok_or(match kind {
+            DeviceKind::Sink => anyhow!("No output"),
+            DeviceKind::Source => anyhow!("No input"),
+        })?;
+        let params = protocol::SetDeviceVolumeParams {
+            device_index: Some(index),
+            device_name: None,
+            volume: Self::from_percent(percent, volume.channels().len()),
+        };
+        self.ack(&match kind {
+            DeviceKind::Sink => Command::SetSinkVolume(params),
+            DeviceKind::Source => Command::SetSourceVolume(params),
+        })?;
+        Ok(())
+    }
+
+    fn set_mute(&self, kind: DeviceKind, muted: bool) -> anyhow::Result<()> {
+        self.ack(&match kind {
+            DeviceKind::Sink => Command::SetSinkMute(protocol::SetDeviceMuteParams {
+                device_index: None,
+                device_name: Some(protocol::DEFAULT_SINK.to_owned()),
+                mute: muted,
+            }),
+            DeviceKind::Source => Command::SetSourceMute(protocol::SetDeviceMuteParams {
+                device_index: None,
+                device_name: Some(protocol::DEFAULT_SOURCE.to_owned()),
+                mute: muted,
+            }),
+        })?;
+        Ok(())
+    }
+
+    fn devices(&self, kind: DeviceKind) -> anyhow::Result<Vec<AudioDevice>> {
+        let server = self.request::<protocol::ServerInfo>(&Command::GetServerInfo)?;
+        let devices = match kind {
+            DeviceKind::Sink => {
+                let default = server.default_sink_name.unwrap_or_default();
+                self.request::<protocol::SinkInfoList>(&Command::GetSinkInfoList)?
+                    .iter()
+                    .map(|s| Self::device(s.index, &s.name, s.description.as_deref(), &default))
+                    .collect()
+            }
+            DeviceKind::Source => {
+                let default = server.default_source_name.unwrap_or_default();
+                self.request::<protocol::SourceInfoList>(&Command::GetSourceInfoList)?
+                    .iter()
+                    // monitors of sinks are not real inputs
+                    .filter(|s| s.monitor_of_sink_index.is_none())
+                    .map(|s| Self::device(s.index, &s.name, s.description.as_deref(), &default))
+                    .collect()
+            }
+        };
+        Ok(devices)
+    }
+
+    fn set_default(&self, kind: DeviceKind, device: &AudioDevice) -> anyhow::Result<()> {
+        let name = CString::new(device.name.as_str())?;
+        self.ack(&match kind {
+            DeviceKind::Sink => Command::SetDefaultSink(name),
+            DeviceKind::Source => Command::SetDefaultSource(name),
+        })?;
+        Ok(())
+    }
+
+    fn streams(&self) -> anyhow::Result<Vec<AudioStream>> {
+        Ok(self
+            .request::<protocol::SinkInputInfoList>(&Command::GetSinkInputInfoList)?
+            .iter()
+            .filter(|s| s.has_volume)
+            .map(|s| AudioStream {
+                id: s.index,
+                app: s
+                    .props
+                    .get(protocol::Prop::ApplicationName)
+                    .map(|app| {
+                        String::from_utf8_lossy(app)
+                            .trim_end_matches('\0')
+                            .to_string()
+                    })
+                    .unwrap_or(s.name.to_string_lossy().to_string()),
+                level: AudioLevel {
+                    volume: Self::to_percent(&s.cvolume),
+                    muted: s.muted,
+                },
+            })
+            .collect())
+    }
+
+    fn set_stream_volume(&self, stream: &AudioStream, percent: u32) -> anyhow::Result<()> {
+        let info =
+            self.request::<protocol::SinkInputInfo>(&Command::GetSinkInputInfo(stream.id))?;
+        self.ack(&Command::SetSinkInputVolume(
+            protocol::SetStreamVolumeParams {
+                index: stream.id,
+                volume: Self::from_percent(percent, info.cvolume.channels().len()),
+            },
+        ))?;
+        Ok(())
+    }
+
+    fn set_stream_mute(&self, stream: &AudioStream, muted: bool) -> anyhow::Result<()> {
+        self.ack(&Command::SetSinkInputMute(protocol::SetStreamMuteParams {
+            index: stream.id,
+            mute: muted,
+        }))?;
+        Ok(())
+    }
+
+    fn subscribe(
+        &self,
+        on_change: &mut dyn FnMut(AudioEvent) -> anyhow::Result<()>,
+    ) -> anyhow::Result<()> {
+        let mask = protocol::SubscriptionMask::SINK
+            | protocol::SubscriptionMask::SOURCE
+            | protocol::SubscriptionMask::SINK_INPUT
+            | protocol::SubscriptionMask::SERVER;
+        self.ack(&Command::Subscribe(mask))?;
+
+        let mut socket = self.socket.borrow_mut();
+        loop {
+            let (_, command) = protocol::read_command_message(&mut *socket, self.version)?;
+            let Command::SubscribeEvent(event) = command else {
+                continue;
+            };
+            match event.event_facility {
+                protocol::SubscriptionEventFacility::Sink => on_change(AudioEvent::Sink)?,
+                protocol::SubscriptionEventFacility::Source => on_change(AudioEvent::Source)?,
+                protocol::SubscriptionEventFacility::SinkInput => on_change(AudioEvent::Stream)?,
+                // the default sink or source may have changed
+                protocol::SubscriptionEventFacility::Server => {
+                    on_change(AudioEvent::Sink)?;
+                    on_change(AudioEvent::Source)?;
+                }
+                _ => (),
+            }
+        }
+    }
+}
@@ -0,0 +1,328 @@
+use std::ffi::OsStr;
+use std::process::{Command, Output};
+
+use super::{AudioBackend, AudioDevice, AudioLevel, AudioStream, DeviceKind};
+
+const PROGRAM: &str = "wpctl";
+const SINK: &str = "@DEFAULT_AUDIO_SINK@";
+const SOURCE: &str = "@DEFAULT_AUDIO_SOURCE@";
+
+pub struct Wpctl;
+
+impl Wpctl {
+    fn exec(args: &[impl AsRef<OsStr>]) -> anyhow::Result<Output> {
+        let output = Command::new(PROGRAM).args(args).output()?;
+        Ok(output)
+    }
+
+    fn default_node(kind: DeviceKind) -> &'static str {
+        match kind {
+            DeviceKind::Sink => SINK,
+            DeviceKind::Source => SOURCE,
+        }
+    }
+
+    /// Returns the level of the node `id`, `None` if it does not exist
+    fn level(id: &str) -> anyhow::Result<Option<AudioLevel>> {
+        let output = Self::exec(&["get-volume", id])?;
+        if !output.stderr.is_empty() {
+            return Ok(None);
+        }
+        Self::parse_level(&String::from_utf8(output.stdout)?).map(Some)
+    }
+
+    /// Parses the output of `wpctl get-volume`, like `Volume: 0.40 [MUTED]`
+    fn parse_level(string: &str) -> anyhow::Result<AudioLevel> {
+        let volume = string
+            .split(' ')
+            .nth(1)
+            .ok_or(anyhow::anyhow!("Invalid wpctl output: '{string}'"))?
+            .trim()
+            .parse::<f32>()?;
+        Ok(AudioLevel {
+            volume: (volume * 100.).round() as u32,
+            muted: string.contains("MUTED"),
+        })
+    }
+
+    fn status_section(header: &str) -> anyhow::Result<Vec<String>> {
+        let output = String::from_utf8(Self::exec(&["status"])?.stdout)?;
+        Ok(Self::section(&output, header))
+    }
+
+    /// Returns the lines of the `header` subsection of the Audio section of `wpctl status`,
+    /// stripped from their tree drawing
+    fn section(status: &str, header: &str) -> Vec<String> {
+        let (mut audio, mut section) = (false, false);
+        let mut lines = Vec::new();
+        for line in status.lines() {
+            // top level sections (Audio, Video, Settings) are not indented
+            if !line.starts_with(' ') && !line.trim().is_empty() {
+                audio = line.trim() == "Audio";
+                section = false;
+                continue;
+            }
+            let line = line.trim_start_matches(|c: char| c.is_whitespace() || "│├└─".contains(c));
+            if line.ends_with(':') {
+                section = audio && line == header;
+            } else if section && !line.is_empty() {
+                lines.push(line.to_string());
+            }
+        }
+        lines
+    }
+
+    /// Returns the identifiers and names of the playback streams among the lines of the Streams
+    /// subsection.
+    /// Streams are followed by their ports, like `50. output_FL > Built-in Audio:playback_FL`,
+    /// playback streams being the ones with output ports.
+    fn playback_streams(lines: &[String]) -> anyhow::Result<Vec<(u32, String)>> {
+        let mut streams: Vec<(u32, String, bool)> = Vec::new();
+        for line in lines {
+            let Some((id, name)) = line.split_once(". ") else {
+                continue;
+            };
+            if name.contains(" > ") {
+                if let Some(stream) = streams.last_mut() {
+                    stream.2 = true;
+                }
+            } else if !name.contains(" < ") {
+                streams.push((id.trim().parse()?, name.trim().to_string(), false));
+            }
+        }
+        Ok(streams
+            .into_iter()
+            .filter(|(_, _, output)| *output)
+            .map(|(id, name, _)| (id, name))
+            .collect())
+    }
+
+    /// Parses a device line of `wpctl status`, like `*   48. Built-in Audio [vol: 0.40]`
+    fn parse_device(line: &str) -> Option<AudioDevice> {
+        let default = line.starts_with('*');
+        let (id, rest) = line.trim_start_matches('*').trim().split_once(". ")?;
+        let name = rest
+            .split_once(" [vol:")
+            .map_or(rest, |(name, _)| name)
+            .trim()
+            .to_string();
+        Some(AudioDevice {
+            id: id.parse().ok()?,
+            description: name.clone(),
+            name,
+            default,
+        })
+    }
+}
+
+impl AudioBackend for Wpctl {
+    fn get(&self, kind: DeviceKind) -> anyhow::Result<Option<AudioLevel>> {
+        Self::level(Self::default_node(kind))
+    }
+
+    fn set_volume(&self, kind: DeviceKind, percent: u32) -> anyhow::Result<()> {
+        Self::exec(&[
+            "set-volume",
+            Self::default_node(kind),
+            &format!("{percent}%"),
+        ])?;
+        Ok(())
+    }
+
+    fn set_mute(&self, kind: DeviceKind, muted: bool) -> anyhow::Result<()> {
+        Self::exec(&[
+            "set-mute",
+            Self::default_node(kind),
+            if muted { "1" } else { "0" },
+        ])?;
+        Ok(())
+    }
+
+    fn devices(&self, kind: DeviceKind) -> anyhow::Result<Vec<AudioDevice>> {
+        let header = match kind {
+            DeviceKind::Sink => "Sinks:",
+            DeviceKind::Source => "Sources:",
+        };
+        Ok(Self::status_section(header)?
+            .iter()
+            .flat_map(|line| Self::parse_device(line))
+            .collect())
+    }
+
+    fn set_default(&self, _: DeviceKind, device: &AudioDevice) -> anyhow::Result<()> {
+        let output = Self::exec(&["set-default", &device.id.to_string()])?;
+        if output.stderr.is_empty() {
+            Ok(())
+        } else {
+            Err(anyhow::anyhow!(String::from_utf8(output.stderr)?))
+        }
+    }
+
+    fn streams(&self) -> anyhow::Result<Vec<AudioStream>> {
+        let mut playback = Vec::new();
+        for (id, app) in Self::playback_streams(&Self::status_section("Streams:")?)? {
+            if let Some(level) = Self::level(&id.to_string())? {
+                playback.push(AudioStream { id, app, level });
+            }
+        }
+        Ok(playback)
+    }
+
+    fn set_stream_volume(&self, stream: &AudioStream, percent: u32) -> anyhow::Result<()> {
+        Self::exec(&["set-volume", &stream.id.to_string(), &format!("{percent}%")])?;
+        Ok(())
+    }
+
+    fn set_stream_mute(&self, stream: &AudioStream, muted: bool) -> anyhow::Result<()> {
+        Self::exec(&[
+            "set-mute",
+            &stream.id.to_string(),
+            if muted { "1" } else { "0" },
+        ])?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `wpctl status` of a laptop playing music in Firefox while Discord records
+    const STATUS: &str = r#"PipeWire 'pipewire-0' [1.0.5, user@laptop, cookie:3184517734]
+ └─ Clients:
+        33. WirePlumber                         [1.0.5, user@laptop, pid:1202]
+        66. Firefox                             [1.0.5, user@laptop, pid:2301]
+
+Audio
+ ├─ Devices:
+ │      42. Built-in Audio                      [alsa]
+ │      43. USB Headset                         [alsa]
+ │  
+ ├─ Sinks:
+ │  *   48. Built-in Audio Analog Stereo        [vol: 0.40]
+ │      52. USB Headset Analog Stereo           [vol: 0.75 MUTED]
+ │  
+ ├─ Sink endpoints:
+ │  
+ ├─ Sources:
+ │  *   49. Built-in Audio Analog Stereo        [vol: 1.00]
+ │  
+ ├─ Source endpoints:
+ │  
+ └─ Streams:
+        70. Firefox
+             71. output_FL       > Built-in Audio Analog Stereo:playback_FL	[active]
+             72. output_FR       > Built-in Audio Analog Stereo:playback_FR	[active]
+        80. Discord
+             81. input_FL        < Built-in Audio Analog Stereo:capture_FL	[active]
+             82. input_FR        < Built-in Audio Analog Stereo:capture_FR	[active]
+        90. speech-dispatcher-dummy
+
+Video
+ ├─ Devices:
+ │      60. Integrated Camera                   [v4l2]
+ │  
+ ├─ Sinks:
+ │  
+ ├─ Sources:
+ │  *   61. Integrated Camera (V4L2)
+ │  
+ ├─ Sink endpoints:
+ │  
+ ├─ Source endpoints:
+ │  
+ └─ Streams:
+
+Settings
+ └─ Default Configured Node Names:
+         0. Audio/Sink    alsa_output.pci-0000_00_1f.3.analog-stereo
+"#;
+
+    fn devices(header: &str) -> Vec<AudioDevice> {
+        Wpctl::section(STATUS, header)
+            .iter()
+            .flat_map(|line| Wpctl::parse_device(line))
+            .collect()
+    }
+
+    #[test]
+    fn section_lists_audio_sinks() {
+        let sinks = devices("Sinks:");
+        let names = sinks.iter().map(|d| d.name.as_str()).collect::<Vec<&str>>();
+        assert_eq!(
+            names,
+            ["Built-in Audio Analog Stereo", "USB Headset Analog Stereo"]
+        );
+        assert_eq!(sinks.iter().map(|d| d.id).collect::<Vec<u32>>(), [48, 52]);
+        assert_eq!(
+            sinks.iter().map(|d| d.default).collect::<Vec<bool>>(),
+            [true, false]
+        );
+    }
+
+    #[test]
+    fn section_leaves_out_video_sources() {
+        let sources = devices("Sources:");
+        assert_eq!(sources.len(), 1);
+        assert_eq!(sources[0].id, 49);
+        assert!(sources[0].default);
+    }
+
+    #[test]
+    fn section_strips_tree_drawing() {
+        let lines = Wpctl::section(STATUS, "Devices:");
+        assert_eq!(
+            lines,
+            [
+                "42. Built-in Audio                      [alsa]",
+                "43. USB Headset                         [alsa]"
+            ]
+        );
+        assert!(Wpctl::section(STATUS, "Sink endpoints:").is_empty());
+        assert!(Wpctl::section(STATUS, "Missing:").is_empty());
+    }
+
+    #[test]
+    fn playback_streams_have_output_ports() {
+        let streams = Wpctl::playback_streams(&Wpctl::section(STATUS, "Streams:")).unwrap();
+        assert_eq!(streams, [(70, "Firefox".to_string())]);
+    }
+
+    #[test]
+    fn parse_level_reads_muted_suffix() {
+        let level = Wpctl::parse_level("Volume: 0.40\n").unwrap();
+        assert_eq!((level.volume, level.muted), (40, false));
+        let level = Wpctl::parse_level("Volume: 0.75 [MUTED]\n").unwrap();
+        assert_eq!((level.volume, level.muted), (75, true));
+        assert!(Wpctl::parse_level("").is_err());
+    }
+
+    #[test]
+    fn parse_device_reads_default_marker() {
+        let device =
+            Wpctl::parse_device("*   48. Built-in Audio Analog Stereo [vol: 0.40]").unwrap();
+        assert_eq!(device.id, 48);
+        assert_eq!(device.name, "Built-in Audio Analog Stereo");
+        assert!(device.default);
+        let device = Wpctl::parse_device("52. USB Headset [vol: 0.75]").unwrap();
+        assert_eq!(device.id, 52);
+        assert!(!device.default);
+    }
+
+    #[test]
+    fn parse_device_strips_volume_suffix() {
+        let device =
+            Wpctl::parse_device("52. USB Headset Analog Stereo        [vol: 0.75 MUTED]").unwrap();
+        assert_eq!(device.name, "USB Headset Analog Stereo");
+        assert_eq!(device.description, device.name);
+        let device = Wpctl::parse_device("61. Integrated Camera (V4L2)").unwrap();
+        assert_eq!(device.name, "Integrated Camera (V4L2)");
+    }
+
+    #[test]
+    fn parse_device_rejects_other_lines() {
+        assert!(Wpctl::parse_device("Sinks:").is_none());
+        assert!(Wpctl::parse_device("abc. Not a device").is_none());
+        assert!(Wpctl::parse_device("").is_none());
+    }
+}
@@ -1,49 +1,506 @@
+pub mod history;
+pub mod limit;
+pub mod upower;
+
+use crate::components::power_profile::{PowerProfile, Profile};
+use crate::components::system::System;
+use crate::config::Config;
 use crate::logger::Logger;
-use crate::notifier::Notifier;
+use crate::notifier::{Notifier, UrgencyLevel};
+use crate::utils::format_duration;
 use anyhow::anyhow;
-use battery::{units::ratio::percent, Battery as Batt, Manager, State};
+use battery::units::{energy::watt_hour, power::watt};
+use battery::{Battery as Batt, Manager, State};
+use clap::Subcommand;
 use notify_rust::Urgency;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::fmt::Display;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 use std::time::Duration;
 
+use self::history::{History, Sample, SAMPLE_INTERVAL};
+use self::{limit::ChargeLimit, upower::UPower};
+
+const JSON_VIEW_NAME: &str = "battery-json";
+const PERIPHERALS_JSON_VIEW_NAME: &str = "battery-peripherals-json";
+/// Percentage under which peripherals are reported as low
+const PERIPHERAL_LOW: u32 = 15;
+
+/// Whether the critical action was triggered during this discharge, kept across restarts of the
+/// listener so that a single countdown runs
+static CRITICAL_REACHED: AtomicBool = AtomicBool::new(false);
+
+/// Battery level at which to warn the user
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct Threshold {
+    pub percent: u32,
+    pub summary: String,
+    pub body: String,
+    #[serde(default)]
+    pub urgency: UrgencyLevel,
+    /// Shell command run when the threshold is reached
+    pub command: Option<String>,
+}
+
+impl Threshold {
+    fn new(percent: u32, summary: &str, body: &str, urgency: UrgencyLevel) -> Self {
+        Self {
+            percent,
+            summary: summary.to_string(),
+            body: body.to_string(),
+            urgency,
+            command: None,
+        }
+    }
+
+    fn reach(&self) -> anyhow::Result<()> {
+        Notifier::new("battery").send(
+            &self.summary,
+            &self.body,
+            Some(self.urgency.into()),
+            None,
+        )?;
+        if let Some(command) = &self.command {
+            let mut child = std::process::Command::new("sh")
+                .args(["-c", command])
+                .spawn()?;
+            // reaped aside so that the daemon neither waits for it nor leaves a zombie
+            std::thread::spawn(move || child.wait());
+        }
+        Ok(())
+    }
+}
+
+/// What to do when the battery is about to die
+#[derive(Deserialize, Serialize, Clone, Copy, Debug)]
+pub enum CriticalAction {
+    Suspend,
+    Hibernate,
+    HybridSleep,
+    SuspendThenHibernate,
+    Poweroff,
+}
+
+impl CriticalAction {
+    fn run(&self) -> anyhow::Result<()> {
+        match self {
+            CriticalAction::Suspend => System::suspend(),
+            CriticalAction::Hibernate => System::hibernate(),
+            CriticalAction::HybridSleep => System::hybrid_sleep(),
+            CriticalAction::SuspendThenHibernate => System::suspend_then_hibernate(),
+            CriticalAction::Poweroff => System::poweroff(),
+        }
+    }
+
+    fn describe(&self) -> &str {
+        match self {
+            CriticalAction::Suspend => "Suspending",
+            CriticalAction::Hibernate => "Hibernating",
+            CriticalAction::HybridSleep => "Going to hybrid sleep",
+            CriticalAction::SuspendThenHibernate => "Suspending",
+            CriticalAction::Poweroff => "Powering off",
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct Critical {
+    pub percent: u32,
+    pub action: CriticalAction,
+    /// Seconds left to cancel the action or plug the charger
+    #[serde(default = "Critical::default_countdown")]
+    pub countdown: u64,
+}
+
+impl Critical {
+    fn default_countdown() -> u64 {
+        60
+    }
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+#[serde(default)]
+pub struct BatteryConfig {
+    /// Each threshold is notified once per discharge, in any order
+    pub thresholds: Vec<Threshold>,
+    /// Action run once per discharge, none by default
+    pub critical: Option<Critical>,
+    /// Charge limit applied when the daemon starts
+    pub limit: Option<ChargeLimit>,
+}
+
+impl Default for BatteryConfig {
+    fn default() -> Self {
+        Self {
+            thresholds: vec![
+                Threshold::new(20, "Battery low", "Connect charger", UrgencyLevel::Normal),
+                Threshold::new(
+                    10,
+                    "Battery very low",
+                    "Connect charger",
+                    UrgencyLevel::Critical,
+                ),
+            ],
+            critical: None,
+            limit: None,
+        }
+    }
+}
+
 pub struct Battery;
 
 impl Battery {
-    pub fn listen() -> anyhow::Result<()> {
-        let (manager, mut battery) = Self::get_battery()?;
-        let mut current = None;
-        loop {
-            let state = Self::get_state(&manager, &mut battery)?;
-            if Some(&state) != current.as_ref() {
-                state.notify()?;
-                state.update_view()?;
-                current = Some(state)
+    /// Refreshes the batteries every time UPower reports a change, or every two seconds when
+    /// UPower is not available
+    pub fn listen(config: &Config) -> anyhow::Result<()> {
+        let mut watcher = Watcher::new(config)?;
+        watcher.refresh()?;
+        let signals = UPower::connect()
+            .ok()
+            .filter(|upower| upower.devices().is_ok())
+            .map(|upower| upower.signals())
+            .transpose()?;
+        match signals {
+            Some(signals) => {
+                for signal in signals {
+                    signal?;
+                    watcher.refresh()?;
+                }
+                Err(anyhow!("Connection to UPower closed"))
+            }
+            None => loop {
+                std::thread::sleep(Duration::from_secs(2));
+                watcher.refresh()?;
+            },
+        }
+    }
+
+    pub fn handle(operation: BatteryOp, config: &Config) -> anyhow::Result<Option<String>> {
+        let (manager, mut batteries) = Self::get_batteries()?;
+        match operation {
+            BatteryOp::Status => {
+                let state = Self::get_state(&config.sysfs_root, &manager, &mut batteries)?;
+                return Ok(Some(state.to_string()));
+            }
+            BatteryOp::History => return Ok(Some(History::new().stats()?.to_string())),
+            BatteryOp::Limit { start, end } => {
+                ChargeLimit::new(start, end)?.write(&config.sysfs_root)?
             }
-            std::thread::sleep(Duration::from_secs(2));
         }
+        Self::get_state(&config.sysfs_root, &manager, &mut batteries)?.update_view()?;
+        Ok(None)
     }
 
-    fn get_state(manager: &Manager, battery: &mut Batt) -> anyhow::Result<BatteryState> {
-        manager.refresh(battery)?;
-        let value = battery.state_of_charge().get::<percent>();
-        let state = battery.state();
-        Ok(BatteryState::new(value.round() as u32, state))
+    /// Applies the charge limit of `config`, if any
+    pub fn apply_limit(config: &Config) -> anyhow::Result<()> {
+        match config.battery.limit {
+            Some(limit) => ChargeLimit::new(limit.start, limit.end)?.write(&config.sysfs_root),
+            None => Ok(()),
+        }
     }
 
-    fn get_battery() -> anyhow::Result<(Manager, Batt)> {
+    /// Runs the critical action after a countdown, unless it is cancelled from the notification
+    /// or the charger gets plugged in.
+    /// Batteries that cannot be read are assumed to be still discharging, as the action is what
+    /// saves the session.
+    fn critical(critical: &Critical, root: &Path) -> anyhow::Result<()> {
+        let summary = "Battery critically low";
+        let body =
+            |left: u64| format!("{} in {left}s, connect charger", critical.action.describe());
+        let mut batteries = Self::get_batteries()
+            .inspect_err(|e| eprintln!("Could not read the batteries: {e}"))
+            .ok();
+        let notifier = Notifier::new("battery");
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let on_cancel = cancelled.clone();
+        if let Err(e) = notifier.send_with_action(
+            summary,
+            &body(critical.countdown),
+            Some(Urgency::Critical),
+            "Cancel",
+            move || on_cancel.store(true, Ordering::Relaxed),
+        ) {
+            eprintln!("Could not notify the critical battery: {e}");
+        }
+        for left in (0..critical.countdown).rev() {
+            std::thread::sleep(Duration::from_secs(1));
+            if cancelled.load(Ordering::Relaxed) {
+                return notifier.send(summary, "Cancelled", None, None);
+            }
+            let discharging = match batteries.as_mut() {
+                Some((manager, batteries)) => Self::get_state(root, manager, batteries)
+                    .map(|state| state.discharging())
+                    .unwrap_or_else(|e| {
+                        eprintln!("Could not read the batteries: {e}");
+                        true
+                    }),
+                None => true,
+            };
+            if !discharging {
+                return Ok(());
+            }
+            if let Err(e) =
+                notifier.update_with_action(summary, &body(left), Some(Urgency::Critical), "Cancel")
+            {
+                eprintln!("Could not notify the critical battery: {e}");
+            }
+        }
+        critical.action.run()
+    }
+
+    /// Combines all the batteries powering the computer in one state, `root` being where sysfs
+    /// is mounted
+    fn get_state(
+        root: &Path,
+        manager: &Manager,
+        batteries: &mut [Batt],
+    ) -> anyhow::Result<BatteryState> {
+        for battery in batteries.iter_mut() {
+            manager.refresh(battery)?;
+        }
+        let energy = batteries
+            .iter()
+            .map(|b| b.energy().get::<watt_hour>())
+            .sum::<f32>();
+        let full = batteries
+            .iter()
+            .map(|b| b.energy_full().get::<watt_hour>())
+            .sum::<f32>();
+        let design = batteries
+            .iter()
+            .map(|b| b.energy_full_design().get::<watt_hour>())
+            .sum::<f32>();
+        let rate = batteries
+            .iter()
+            .map(|b| b.energy_rate().get::<watt>().abs())
+            .sum::<f32>();
+        let value = (if full > 0. { energy * 100. / full } else { 0. }).round() as u32;
+        let state = Self::combine(batteries.iter().map(Batt::state));
+
+        // hours to seconds
+        let time = |energy: f32| (rate > 0.).then(|| (energy / rate * 3600.).round() as u64);
+        let (time_to_empty, time_to_full) = match state {
+            State::Discharging => (time(energy), None),
+            State::Charging => (None, time(full - energy)),
+            _ => (None, None),
+        };
+        Ok(BatteryState {
+            value,
+            status: status(state),
+            icon: icon(value, state),
+            time_to_empty,
+            time_to_full,
+            energy_rate: (rate * 10.).round() / 10.,
+            health: (design > 0.).then(|| (full * 100. / design).round() as u32),
+            cycle_count: batteries.iter().flat_map(Batt::cycle_count).max(),
+            charge_limit: ChargeLimit::read(root)?,
+        })
+    }
+
+    /// The computer is charging if any battery is, discharging if any battery is, and so on
+    fn combine(states: impl Iterator<Item = State>) -> State {
+        let states = states.collect::<Vec<State>>();
+        [State::Charging, State::Discharging]
+            .into_iter()
+            .find(|state| states.contains(state))
+            .or(
+                (!states.is_empty() && states.iter().all(|s| *s == State::Full))
+                    .then_some(State::Full),
+            )
+            .unwrap_or(states.first().copied().unwrap_or(State::Unknown))
+    }
+
+    fn get_batteries() -> anyhow::Result<(Manager, Vec<Batt>)> {
         let manager = Manager::new()?;
-        let battery = manager
-            .batteries()?
-            .flatten()
-            .next()
-            .ok_or(anyhow!("No battery found"))?;
-        Ok((manager, battery))
+        let batteries = manager.batteries()?.flatten().collect::<Vec<Batt>>();
+        if batteries.is_empty() {
+            return Err(anyhow!("No battery found"));
+        }
+        Ok((manager, batteries))
+    }
+
+    /// Batteries of the peripherals, none if UPower is not available
+    fn get_peripherals(upower: Option<&UPower>) -> Vec<PeripheralState> {
+        upower
+            .and_then(|upower| upower.peripherals().ok())
+            .unwrap_or_default()
+            .into_iter()
+            .map(|device| PeripheralState {
+                value: device.percentage.round() as u32,
+                status: status(device.state),
+                icon: icon(device.percentage.round() as u32, device.state),
+                path: device.path,
+                kind: device.kind,
+                model: device.model,
+            })
+            .collect()
+    }
+
+    pub(crate) fn init_view(config: &Config) -> anyhow::Result<()> {
+        let (manager, mut batteries) = Self::get_batteries()?;
+        Self::get_state(&config.sysfs_root, &manager, &mut batteries)?.update_view()?;
+        let upower = UPower::connect().ok();
+        Logger::new(PERIPHERALS_JSON_VIEW_NAME).send(&Self::get_peripherals(upower.as_ref()))
+    }
+}
+
+/// What the battery listener remembers between refreshes
+struct Watcher<'a> {
+    config: &'a Config,
+    manager: Manager,
+    batteries: Vec<Batt>,
+    upower: Option<UPower>,
+    /// Sorted from the highest percentage
+    thresholds: Vec<Threshold>,
+    /// Thresholds already reached during this discharge
+    reached: usize,
+    saver_reached: bool,
+    current: Option<BatteryState>,
+    peripherals: Vec<PeripheralState>,
+    /// Peripherals already notified as low
+    low: BTreeSet<String>,
+    history: History,
+    last_sample: Option<Sample>,
+}
+
+impl<'a> Watcher<'a> {
+    fn new(config: &'a Config) -> anyhow::Result<Self> {
+        let (manager, batteries) = Battery::get_batteries()?;
+        let mut thresholds = config.battery.thresholds.clone();
+        thresholds.sort_by_key(|t| std::cmp::Reverse(t.percent));
+        Ok(Self {
+            config,
+            manager,
+            batteries,
+            upower: UPower::connect().ok(),
+            thresholds,
+            reached: 0,
+            saver_reached: false,
+            current: None,
+            peripherals: Vec::new(),
+            low: BTreeSet::new(),
+            history: History::new(),
+            last_sample: None,
+        })
+    }
+
+    fn refresh(&mut self) -> anyhow::Result<()> {
+        let state =
+            Battery::get_state(&self.config.sysfs_root, &self.manager, &mut self.batteries)?;
+        if self.current.as_ref() != Some(&state) {
+            if let Some(current) = &self.current {
+                if current.discharging() != state.discharging() {
+                    state.notify_charger()?;
+                }
+            }
+            if state.discharging() {
+                let crossed = self
+                    .thresholds
+                    .iter()
+                    .filter(|t| state.value <= t.percent)
+                    .count();
+                // only the lowest one when several are crossed at once
+                if crossed > self.reached {
+                    self.thresholds[crossed - 1].reach()?;
+                    self.reached = crossed;
+                }
+                if let Some(critical) = &self.config.battery.critical {
+                    if state.value <= critical.percent
+                        && !CRITICAL_REACHED.swap(true, Ordering::Relaxed)
+                    {
+                        // the countdown must not hold back the listener, nor its failure kill it
+                        let (critical, root) = (critical.clone(), self.config.sysfs_root.clone());
+                        std::thread::spawn(move || {
+                            if let Err(e) = Battery::critical(&critical, &root) {
+                                eprintln!("Critical battery action failed: {e}");
+                            }
+                        });
+                    }
+                }
+                if let Some(percent) = self.config.power_profile.saver_below {
+                    if !self.saver_reached && state.value <= percent {
+                        self.saver_reached = true;
+                        // power-profiles-daemon is optional
+                        if let Err(e) = PowerProfile::new().and_then(|p| p.set(Profile::PowerSaver))
+                        {
+                            eprintln!("Could not switch to power-saver: {e}");
+                        }
+                    }
+                }
+            } else {
+                self.reached = 0;
+                CRITICAL_REACHED.store(false, Ordering::Relaxed);
+                self.saver_reached = false;
+            }
+            state.update_view()?;
+            self.sample(&state)?;
+            self.current = Some(state)
+        }
+
+        let peripherals = Battery::get_peripherals(self.upower.as_ref());
+        if peripherals != self.peripherals {
+            for peripheral in &peripherals {
+                if peripheral.value > PERIPHERAL_LOW || peripheral.status == "Charging" {
+                    self.low.remove(&peripheral.path);
+                } else if self.low.insert(peripheral.path.clone()) {
+                    peripheral.notify()?;
+                }
+            }
+            Logger::new(PERIPHERALS_JSON_VIEW_NAME).send(&peripherals)?;
+            self.peripherals = peripherals;
+        }
+        Ok(())
+    }
+
+    /// Records `state` in the history if the last sample is old enough or the status changed
+    fn sample(&mut self, state: &BatteryState) -> anyhow::Result<()> {
+        let sample = Sample::now(state.value, state.discharging(), state.energy_rate);
+        if self.last_sample.as_ref().is_some_and(|last| {
+            // a clock going backwards starts a new interval
+            (last.time..last.time + SAMPLE_INTERVAL).contains(&sample.time)
+                && sample.discharging == last.discharging
+        }) {
+            return Ok(());
+        }
+        self.history.record(&sample)?;
+        Logger::new(history::JSON_VIEW_NAME).send(&self.history.stats()?)?;
+        self.last_sample = Some(sample);
+        Ok(())
     }
+}
+
+fn icon(value: u32, state: State) -> String {
+    if state == State::Charging {
+        " "
+    } else if state == State::Full || state == State::Unknown {
+        " "
+    } else if value >= 85 {
+        " "
+    } else if value >= 60 {
+        " "
+    } else if value >= 40 {
+        " "
+    } else if value >= 15 {
+        " "
+    } else {
+        " "
+    }
+    .to_string()
+}
 
-    pub(crate) fn init_view() -> anyhow::Result<()> {
-        let (mut manager, mut battery) = Self::get_battery()?;
-        Self::get_state(&mut manager, &mut battery)?.update_view()
+fn status(state: State) -> String {
+    match state {
+        State::Unknown => "Not charging",
+        State::Charging => "Charging",
+        State::Discharging => "Discharging",
+        State::Empty => "Empty",
+        State::Full => "Full",
+        _ => "Unknown",
     }
+    .to_string()
 }
 
 #[derive(Serialize, Deserialize, PartialEq)]
@@ -51,55 +508,100 @@ struct BatteryState {
     value: u32,
     status: String,
     icon: String,
+    /// Seconds until the batteries are empty, when discharging
+    time_to_empty: Option<u64>,
+    /// Seconds until the batteries are full, when charging
+    time_to_full: Option<u64>,
+    /// Power drawn or received, in watts
+    energy_rate: f32,
+    /// Capacity left compared to when the batteries were new, in percent
+    health: Option<u32>,
+    /// Highest charge cycle count among the batteries
+    cycle_count: Option<u32>,
+    charge_limit: Option<ChargeLimit>,
 }
 
 impl BatteryState {
-    pub fn new(value: u32, state: State) -> Self {
-        let icon = if state == State::Charging {
-            " "
-        } else if state == State::Full || state == State::Unknown {
-            " "
-        } else if value >= 85 {
-            " "
-        } else if value >= 60 {
-            " "
-        } else if value >= 40 {
-            " "
-        } else if value >= 15 {
-            " "
+    fn discharging(&self) -> bool {
+        self.status == "Discharging"
+    }
+
+    fn notify_charger(&self) -> anyhow::Result<()> {
+        let summary = if self.discharging() {
+            "Charger disconnected"
         } else {
-            " "
-        }
-        .to_string();
-        let status = match state {
-            State::Unknown => "Not charging",
-            State::Charging => "Charging",
-            State::Discharging => "Discharging",
-            State::Empty => "Empty",
-            State::Full => "Full",
-            _ => "Unknown",
-        }
-        .to_string();
-        Self {
-            value,
-            status,
-            icon,
-        }
+            "Charger connected"
+        };
+        Notifier::new("battery").send(
+            summary,
+            &format!("Battery at {}%", self.value),
+            None,
+            Some(self.value),
+        )
+    }
+
+    pub fn update_view(&self) -> anyhow::Result<()> {
+        Logger::new(JSON_VIEW_NAME).send(self)
     }
+}
 
-    pub fn notify(&self) -> anyhow::Result<()> {
-        if self.status != "Charging" && self.value <= 10 {
-            Notifier::new("battery").send(
-                "Battery very low",
-                "Connect charger",
-                Some(Urgency::Critical),
-                None,
-            )?;
+impl Display for BatteryState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Battery: {}% ({})", self.value, self.status)?;
+        let rate = format!("{:.1} W", self.energy_rate);
+        if let Some(time) = self.time_to_empty {
+            let time = format_duration(Duration::from_secs(time));
+            write!(f, "\nRemaining: {time} at {rate}")?;
+        } else if let Some(time) = self.time_to_full {
+            let time = format_duration(Duration::from_secs(time));
+            write!(f, "\nUntil full: {time} at {rate}")?;
+        }
+        if let Some(health) = self.health {
+            write!(f, "\nHealth: {health}%")?;
+        }
+        if let Some(cycle_count) = self.cycle_count {
+            write!(f, "\nCycles: {cycle_count}")?;
+        }
+        if let Some(limit) = self.charge_limit {
+            write!(f, "\nCharge limit: {}-{}%", limit.start, limit.end)?;
         }
         Ok(())
     }
+}
 
-    pub fn update_view(&self) -> anyhow::Result<()> {
-        Logger::new("battery-json").send(self)
+#[derive(Copy, Clone, Subcommand, Serialize, Deserialize)]
+pub enum BatteryOp {
+    /// Print charge, time estimate, power draw, health and cycle count
+    Status,
+    /// Print discharge statistics from the recorded history
+    History,
+    /// Only charge between start and end percentages
+    Limit {
+        #[arg(value_parser = clap::value_parser!(u32).range(0..=100))]
+        start: u32,
+        #[arg(value_parser = clap::value_parser!(u32).range(0..=100))]
+        end: u32,
+    },
+}
+
+/// Battery of a mouse, headset, etc.
+#[derive(Serialize, Deserialize, PartialEq)]
+struct PeripheralState {
+    path: String,
+    kind: String,
+    model: String,
+    value: u32,
+    status: String,
+    icon: String,
+}
+
+impl PeripheralState {
+    fn notify(&self) -> anyhow::Result<()> {
+        Notifier::new("battery-peripheral").send(
+            &format!("{} battery low", self.model),
+            &format!("{}% remaining", self.value),
+            Some(Urgency::Normal),
+            Some(self.value),
+        )
     }
 }
@@ -0,0 +1,291 @@
+use std::fmt::Display;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::logger::Logger;
+use crate::utils::format_duration;
+
+const FILE_NAME: &str = "battery-history";
+pub const JSON_VIEW_NAME: &str = "battery-history-json";
+/// Size after which the history is rotated
+const MAX_SIZE: u64 = 2_u64.pow(18);
+/// Seconds between samples while nothing else changes
+pub const SAMPLE_INTERVAL: u64 = 60;
+/// Longer gaps between samples, like suspends, are not counted as discharging
+const MAX_GAP: u64 = 10 * 60;
+/// Samples shown in the sparkline
+const RECENT: usize = 48;
+const SPARKS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct Sample {
+    /// Seconds since the Unix epoch
+    pub time: u64,
+    pub value: u32,
+    pub discharging: bool,
+    /// In watts
+    pub energy_rate: f32,
+}
+
+impl Sample {
+    pub fn now(value: u32, discharging: bool, energy_rate: f32) -> Self {
+        Self {
+            time: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
+            value,
+            discharging,
+            energy_rate,
+        }
+    }
+}
+
+pub struct History {
+    logger: Logger<Sample>,
+}
+
+impl History {
+    pub fn new() -> Self {
+        Self {
+            logger: Logger::new(FILE_NAME),
+        }
+    }
+
+    pub fn record(&self, sample: &Sample) -> anyhow::Result<()> {
+        self.logger.append(sample, MAX_SIZE)
+    }
+
+    pub fn stats(&self) -> anyhow::Result<HistoryStats> {
+        Ok(HistoryStats::new(&self.logger.read_all()?))
+    }
+}
+
+impl Default for History {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The last discharge
+#[derive(Serialize, Deserialize)]
+pub struct Session {
+    /// Seconds since the Unix epoch
+    pub start: u64,
+    /// In seconds
+    pub duration: u64,
+    pub start_value: u32,
+    pub value: u32,
+    /// Percents per hour
+    pub drain_rate: Option<f32>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct HistoryStats {
+    /// Percents per hour, over all the recorded discharges
+    pub drain_rate: Option<f32>,
+    /// Average power draw while discharging, in watts
+    pub power: Option<f32>,
+    pub session: Option<Session>,
+    /// Last percentages, oldest first
+    pub recent: Vec<u32>,
+}
+
+impl HistoryStats {
+    fn new(samples: &[Sample]) -> Self {
+        let discharging = samples
+            .iter()
+            .filter(|s| s.discharging && s.energy_rate > 0.)
+            .map(|s| s.energy_rate)
+            .collect::<Vec<f32>>();
+        let session = samples
+            .iter()
+            .rposition(|s| s.discharging)
+            .map(|end| {
+                let start = samples[..end]
+                    .iter()
+                    .rposition(|s| !s.discharging)
+                    .map_or(0, |i| i + 1);
+                &samples[start..=end]
+            })
+            .map(|session| Session {
+                start: session[0].time,
+                duration: session[session.len() - 1]
+                    .time
+                    .saturating_sub(session[0].time),
+                start_value: session[0].value,
+                value: session[session.len() - 1].value,
+                drain_rate: Self::drain_rate(session),
+            });
+        Self {
+            drain_rate: Self::drain_rate(samples),
+            power: (!discharging.is_empty())
+                .then(|| discharging.iter().sum::<f32>() / discharging.len() as f32),
+            session,
+            recent: samples
+                .iter()
+                .skip(samples.len().saturating_sub(RECENT))
+                .map(|s| s.value)
+                .collect(),
+        }
+    }
+
+    /// Percents lost per hour between consecutive discharging samples
+    fn drain_rate(samples: &[Sample]) -> Option<f32> {
+        let (drained, seconds) = samples
+            .windows(2)
+            .filter(|w| w[0].discharging && w[1].discharging)
+            // out of order samples come from the clock going backwards
+            .filter(|w| {
+                w[1].time
+                    .checked_sub(w[0].time)
+                    .is_some_and(|gap| gap > 0 && gap <= MAX_GAP)
+            })
+            .fold((0., 0), |(drained, seconds), w| {
+                (
+                    drained + w[0].value as f32 - w[1].value as f32,
+                    seconds + w[1].time.saturating_sub(w[0].time),
+                )
+            });
+        (seconds > 0).then(|| drained * 3600. / seconds as f32)
+    }
+
+    pub fn sparkline(&self) -> String {
+        let (min, max) = (
+            self.recent.iter().min().copied().unwrap_or(0),
+            self.recent.iter().max().copied().unwrap_or(0),
+        );
+        self.recent
+            .iter()
+            .map(|value| {
+                let index =
+                    (value - min) as usize * (SPARKS.len() - 1) / (max - min).max(1) as usize;
+                SPARKS[index]
+            })
+            .collect()
+    }
+}
+
+impl Display for HistoryStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (self.drain_rate, self.power) {
+            (Some(rate), Some(power)) => {
+                write!(f, "Average discharge: {rate:.1}%/h at {power:.1} W")?
+            }
+            (Some(rate), None) => write!(f, "Average discharge: {rate:.1}%/h")?,
+            _ => write!(f, "Average discharge: unknown")?,
+        }
+        if let Some(session) = &self.session {
+            write!(
+                f,
+                "\nLast discharge: {}% to {}% in {}",
+                session.start_value,
+                session.value,
+                format_duration(Duration::from_secs(session.duration))
+            )?;
+            if let Some(rate) = session.drain_rate {
+                write!(f, " ({rate:.1}%/h)")?;
+            }
+        }
+        if !self.recent.is_empty() {
+            write!(f, "\n{}", self.sparkline())?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(time: u64, value: u32, discharging: bool) -> Sample {
+        Sample {
+            time,
+            value,
+            discharging,
+            energy_rate: if discharging { 10. } else { 0. },
+        }
+    }
+
+    #[test]
+    fn drain_rate_over_discharging_samples() {
+        let samples = [
+            sample(0, 80, true),
+            sample(300, 79, true),
+            sample(600, 78, true),
+        ];
+        assert_eq!(HistoryStats::drain_rate(&samples), Some(12.));
+    }
+
+    #[test]
+    fn drain_rate_skips_gaps_and_charging() {
+        let samples = [
+            sample(0, 80, true),
+            sample(600, 78, true),
+            // suspended for an hour
+            sample(600 + 3600, 70, true),
+            sample(600 + 3600 + 60, 70, false),
+            sample(600 + 3600 + 120, 75, false),
+        ];
+        assert_eq!(HistoryStats::drain_rate(&samples), Some(12.));
+        assert_eq!(HistoryStats::drain_rate(&samples[3..]), None);
+    }
+
+    #[test]
+    fn drain_rate_skips_clock_going_backwards() {
+        let samples = [
+            sample(1000, 80, true),
+            sample(400, 79, true),
+            sample(700, 78, true),
+        ];
+        assert_eq!(HistoryStats::drain_rate(&samples), Some(12.));
+        assert_eq!(HistoryStats::drain_rate(&samples[..2]), None);
+    }
+
+    #[test]
+    fn new_finds_last_session() {
+        let samples = [
+            sample(0, 50, true),
+            sample(60, 60, false),
+            sample(120, 90, true),
+            sample(420, 89, true),
+            sample(720, 88, true),
+        ];
+        let stats = HistoryStats::new(&samples);
+        let session = stats.session.unwrap();
+        assert_eq!((session.start, session.duration), (120, 600));
+        assert_eq!((session.start_value, session.value), (90, 88));
+        assert_eq!(session.drain_rate, Some(12.));
+        assert_eq!(stats.power, Some(10.));
+        assert_eq!(stats.recent, [50, 60, 90, 89, 88]);
+    }
+
+    #[test]
+    fn new_survives_clock_going_backwards() {
+        let samples = [sample(1000, 80, true), sample(400, 79, true)];
+        let session = HistoryStats::new(&samples).session.unwrap();
+        assert_eq!(session.duration, 0);
+        assert_eq!(session.drain_rate, None);
+    }
+
+    #[test]
+    fn new_without_samples() {
+        let stats = HistoryStats::new(&[]);
+        assert!(stats.session.is_none());
+        assert_eq!((stats.drain_rate, stats.power), (None, None));
+        assert_eq!(stats.sparkline(), "");
+    }
+
+    #[test]
+    fn sparkline_scales_between_min_and_max() {
+        let stats = HistoryStats::new(&[
+            sample(0, 20, true),
+            sample(60, 90, false),
+            sample(120, 55, false),
+        ]);
+        assert_eq!(stats.sparkline(), "▁█▄");
+        let flat = HistoryStats::new(&[sample(0, 50, false), sample(60, 50, false)]);
+        assert_eq!(flat.sparkline(), "▁▁");
+    }
+}
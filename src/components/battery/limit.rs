@@ -0,0 +1,185 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::anyhow;
+use serde::{Deserialize, Serialize};
+
+const START_FILE: &str = "charge_control_start_threshold";
+const END_FILE: &str = "charge_control_end_threshold";
+
+/// Percentages between which the batteries charge, to make them last longer
+#[derive(Deserialize, Serialize, Clone, Copy, PartialEq, Debug)]
+pub struct ChargeLimit {
+    /// Charging starts below it
+    pub start: u32,
+    /// Charging stops at it
+    pub end: u32,
+}
+
+impl ChargeLimit {
+    pub fn new(start: u32, end: u32) -> anyhow::Result<Self> {
+        if start >= end || end > 100 {
+            return Err(anyhow!(
+                "Invalid charge limit {start}-{end}%, start must be lower than end"
+            ));
+        }
+        Ok(Self { start, end })
+    }
+
+    /// Returns the limit of the first battery supporting it, under the sysfs mounted at `root`
+    pub fn read(root: &Path) -> anyhow::Result<Option<Self>> {
+        let Some(battery) = Self::batteries(root)?.into_iter().next() else {
+            return Ok(None);
+        };
+        Ok(Some(Self {
+            start: Self::read_file(&battery.join(START_FILE))?,
+            end: Self::read_file(&battery.join(END_FILE))?,
+        }))
+    }
+
+    /// Applies the limit to all the batteries supporting it
+    pub fn write(&self, root: &Path) -> anyhow::Result<()> {
+        let batteries = Self::batteries(root)?;
+        if batteries.is_empty() {
+            return Err(anyhow!("No battery supports charge limits"));
+        }
+        for battery in batteries {
+            let end = Self::read_file(&battery.join(END_FILE))?;
+            for (file, value) in self.files(end) {
+                let path = battery.join(file);
+                std::fs::write(&path, value.to_string()).map_err(|e| match e.kind() {
+                    std::io::ErrorKind::PermissionDenied => anyhow!(
+                        "Could not write {}: permission denied, a udev rule must let your user \
+                         write charge thresholds (see the README)",
+                        path.display()
+                    ),
+                    _ => anyhow!("Could not write {}: {e}", path.display()),
+                })?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the files to write in order, given the `end` currently applied.
+    /// The kernel rejects a start above the current end, and the other way around.
+    fn files(&self, end: u32) -> [(&'static str, u32); 2] {
+        if self.start >= end {
+            [(END_FILE, self.end), (START_FILE, self.start)]
+        } else {
+            [(START_FILE, self.start), (END_FILE, self.end)]
+        }
+    }
+
+    /// Returns the batteries with charge thresholds, sorted by name
+    fn batteries(root: &Path) -> anyhow::Result<Vec<PathBuf>> {
+        let dir = root.join("class/power_supply");
+        if !dir.exists() {
+            return Ok(Vec::new());
+        }
+        let mut batteries = std::fs::read_dir(dir)?
+            .map(|entry| entry.map(|e| e.path()))
+            .collect::<Result<Vec<PathBuf>, _>>()?
+            .into_iter()
+            .filter(|path| {
+                path.file_name()
+                    .is_some_and(|name| name.to_string_lossy().starts_with("BAT"))
+                    && path.join(START_FILE).exists()
+                    && path.join(END_FILE).exists()
+            })
+            .collect::<Vec<PathBuf>>();
+        batteries.sort();
+        Ok(batteries)
+    }
+
+    fn read_file(path: &Path) -> anyhow::Result<u32> {
+        std::fs::read_to_string(path)?
+            .trim()
+            .parse()
+            .map_err(|e| anyhow!("Invalid value in {}: {e}", path.display()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fake_sysfs::FakeSysfs;
+
+    /// Builds a fake sysfs with `batteries`, given with their thresholds if they support them
+    fn with_batteries(name: &str, batteries: &[(&str, Option<(u32, u32)>)]) -> FakeSysfs {
+        let mut sysfs = FakeSysfs::new(&format!("limit-{name}"));
+        for (battery, thresholds) in batteries {
+            let dir = format!("class/power_supply/{battery}");
+            sysfs = match thresholds {
+                Some((start, end)) => sysfs
+                    .file(&format!("{dir}/{START_FILE}"), start)
+                    .file(&format!("{dir}/{END_FILE}"), end),
+                None => sysfs.dir(&dir),
+            };
+        }
+        sysfs
+    }
+
+    fn thresholds(sysfs: &FakeSysfs, battery: &str) -> (u32, u32) {
+        let dir = format!("class/power_supply/{battery}");
+        (
+            ChargeLimit::read_file(&sysfs.path(&format!("{dir}/{START_FILE}"))).unwrap(),
+            ChargeLimit::read_file(&sysfs.path(&format!("{dir}/{END_FILE}"))).unwrap(),
+        )
+    }
+
+    #[test]
+    fn new_validates_bounds() {
+        assert!(ChargeLimit::new(40, 80).is_ok());
+        assert!(ChargeLimit::new(80, 80).is_err());
+        assert!(ChargeLimit::new(90, 80).is_err());
+        assert!(ChargeLimit::new(40, 101).is_err());
+    }
+
+    #[test]
+    fn read_uses_first_supporting_battery() {
+        let sysfs = with_batteries(
+            "read",
+            &[("AC", None), ("BAT0", None), ("BAT1", Some((40, 80)))],
+        );
+        assert_eq!(
+            ChargeLimit::read(sysfs.root()).unwrap(),
+            Some(ChargeLimit { start: 40, end: 80 })
+        );
+    }
+
+    #[test]
+    fn read_without_support() {
+        let sysfs = with_batteries("unsupported", &[("BAT0", None)]);
+        assert_eq!(ChargeLimit::read(sysfs.root()).unwrap(), None);
+        assert!(ChargeLimit::new(40, 80)
+            .unwrap()
+            .write(sysfs.root())
+            .is_err());
+    }
+
+    #[test]
+    fn write_applies_to_all_batteries() {
+        let sysfs = with_batteries(
+            "write",
+            &[("BAT0", Some((0, 100))), ("BAT1", Some((75, 80)))],
+        );
+        ChargeLimit::new(50, 60)
+            .unwrap()
+            .write(sysfs.root())
+            .unwrap();
+        assert_eq!(thresholds(&sysfs, "BAT0"), (50, 60));
+        assert_eq!(thresholds(&sysfs, "BAT1"), (50, 60));
+    }
+
+    #[test]
+    fn files_order_keeps_start_below_end() {
+        // raising above the current end: end first
+        let limit = ChargeLimit::new(85, 95).unwrap();
+        assert_eq!(limit.files(80), [(END_FILE, 95), (START_FILE, 85)]);
+        // lowering: start first, so that the end never goes below it
+        let limit = ChargeLimit::new(40, 50).unwrap();
+        assert_eq!(limit.files(80), [(START_FILE, 40), (END_FILE, 50)]);
+        // start equal to the current end would be rejected too
+        let limit = ChargeLimit::new(80, 90).unwrap();
+        assert_eq!(limit.files(80), [(END_FILE, 90), (START_FILE, 80)]);
+    }
+}
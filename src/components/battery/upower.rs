@@ -0,0 +1,131 @@
+use std::collections::HashMap;
+
+use anyhow::anyhow;
+use battery::State;
+use zbus::blocking::{fdo::PropertiesProxy, Connection, MessageIterator};
+use zbus::names::InterfaceName;
+use zbus::zvariant::{OwnedObjectPath, OwnedValue};
+use zbus::{MatchRule, MessageType};
+
+pub const DESTINATION: &str = "org.freedesktop.UPower";
+pub const PATH: &str = "/org/freedesktop/UPower";
+pub const DEVICE_INTERFACE: &str = "org.freedesktop.UPower.Device";
+/// `Type` of the AC adapters
+const LINE_POWER: u32 = 1;
+
+/// A device reported by UPower
+pub struct UPowerDevice {
+    pub path: String,
+    pub kind: String,
+    pub model: String,
+    pub percentage: f64,
+    pub state: State,
+    /// Whether it powers the computer
+    pub power_supply: bool,
+}
+
+pub struct UPower {
+    connection: Connection,
+}
+
+impl UPower {
+    pub fn connect() -> anyhow::Result<Self> {
+        Ok(Self {
+            connection: Connection::system()?,
+        })
+    }
+
+    pub fn devices(&self) -> anyhow::Result<Vec<UPowerDevice>> {
+        let reply = self.connection.call_method(
+            Some(DESTINATION),
+            PATH,
+            Some(DESTINATION),
+            "EnumerateDevices",
+            &(),
+        )?;
+        let mut devices = Vec::new();
+        for path in reply.body::<Vec<OwnedObjectPath>>()? {
+            let properties = PropertiesProxy::builder(&self.connection)
+                .destination(DESTINATION)?
+                .path(path.clone())?
+                .build()?
+                .get_all(InterfaceName::try_from(DEVICE_INTERFACE)?)?;
+            if !Self::property::<bool>(&properties, "IsPresent").unwrap_or(true)
+                || Self::property::<u32>(&properties, "Type")? == LINE_POWER
+            {
+                continue;
+            }
+            devices.push(UPowerDevice {
+                path: path.to_string(),
+                kind: Self::kind(Self::property(&properties, "Type")?).to_string(),
+                model: Self::property(&properties, "Model")?,
+                percentage: Self::property(&properties, "Percentage")?,
+                state: Self::state(Self::property(&properties, "State")?),
+                power_supply: Self::property(&properties, "PowerSupply")?,
+            });
+        }
+        Ok(devices)
+    }
+
+    /// Signals sent by UPower and its devices, like `PropertiesChanged` or `DeviceAdded`
+    pub fn signals(&self) -> anyhow::Result<MessageIterator> {
+        let rule = MatchRule::builder()
+            .msg_type(MessageType::Signal)
+            .sender(DESTINATION)?
+            .path_namespace(PATH)?
+            .build();
+        Ok(MessageIterator::for_match_rule(
+            rule,
+            &self.connection,
+            None,
+        )?)
+    }
+
+    /// Batteries of devices which do not power the computer, like mice or headsets
+    pub fn peripherals(&self) -> anyhow::Result<Vec<UPowerDevice>> {
+        Ok(self
+            .devices()?
+            .into_iter()
+            .filter(|device| !device.power_supply)
+            .collect())
+    }
+
+    fn property<T: TryFrom<OwnedValue>>(
+        properties: &HashMap<String, OwnedValue>,
+        name: &str,
+    ) -> anyhow::Result<T> {
+        properties
+            .get(name)
+            .cloned()
+            .and_then(|value| T::try_from(value).ok())
+            .ok_or(anyhow!("Invalid UPower property '{name}'"))
+    }
+
+    fn kind(kind: u32) -> &'static str {
+        match kind {
+            2 => "battery",
+            3 => "ups",
+            5 => "mouse",
+            6 => "keyboard",
+            8 => "phone",
+            10 => "tablet",
+            12 => "gamepad",
+            13 => "pen",
+            14 => "touchpad",
+            17 => "headset",
+            18 => "speakers",
+            19 => "headphones",
+            _ => "device",
+        }
+    }
+
+    fn state(state: u32) -> State {
+        match state {
+            1 | 5 => State::Charging,
+            2 | 6 => State::Discharging,
+            3 => State::Empty,
+            4 => State::Full,
+            _ => State::Unknown,
+        }
+    }
+}
@@ -1,66 +1,314 @@
+pub mod ddc;
+pub mod sysfs;
+
+use std::fmt::Display;
+use std::time::Duration;
+
+use crate::config::Config;
+use crate::logger::Logger;
 use crate::notifier::Notifier;
-use crate::utils::utf8_to_u32;
+use anyhow::anyhow;
 use clap::Subcommand;
 use serde::{Deserialize, Serialize};
-use std::ffi::OsStr;
-use std::process::{Command, Output};
 
+use self::{ddc::Ddc, sysfs::Backlight};
 use super::components::{NotifiableState, ScaledComponent};
+use super::fade::Fade;
 
-const PROGRAM: &str = "brightnessctl";
 const JSON_VIEW_NAME: &str = "brightness-json";
 
-pub struct Brightness;
+/// What a brightness device lights
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum BacklightKind {
+    Display,
+    Keyboard,
+    External,
+}
+
+impl Display for BacklightKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BacklightKind::Display => write!(f, "display"),
+            BacklightKind::Keyboard => write!(f, "keyboard"),
+            BacklightKind::External => write!(f, "external"),
+        }
+    }
+}
+
+/// Something whose brightness can be changed, values are raw
+pub trait BrightnessDevice {
+    /// Unique identifier, like `backlight/intel_backlight`
+    fn id(&self) -> String;
+    fn name(&self) -> String;
+    fn kind(&self) -> BacklightKind;
+    fn get(&self) -> anyhow::Result<u32>;
+    fn max(&self) -> anyhow::Result<u32>;
+    fn set(&self, value: u32) -> anyhow::Result<()>;
+}
+
+/// How percentages map to raw brightness
+#[derive(Deserialize, Serialize, Clone, Debug)]
+#[serde(default)]
+pub struct BrightnessCurve {
+    /// Exponent applied to percentages, 1 is linear and around 2 feels linear to the eye
+    pub gamma: f32,
+    /// Lowest raw percentage displays can be set to, so that they never turn black
+    pub min: u32,
+}
+
+impl BrightnessCurve {
+    /// Rejects curves that would not map percentages to brightness monotonically
+    pub fn validate(&self) -> anyhow::Result<()> {
+        if !(self.gamma.is_finite() && self.gamma > 0.) {
+            return Err(anyhow!(
+                "Invalid brightness gamma {}, it must be a positive number",
+                self.gamma
+            ));
+        }
+        Ok(())
+    }
+
+    /// Returns the perceived percentage of a device at raw `value` out of `max`
+    pub fn perceived(&self, value: u32, max: u32) -> u32 {
+        if max == 0 {
+            return 0;
+        }
+        let raw = value as f32 / max as f32;
+        (raw.powf(1. / self.gamma) * 100.).round() as u32
+    }
+
+    /// Returns the raw value out of `max` for a device of `kind` to be perceived at `percent`
+    pub fn raw(&self, percent: u32, max: u32, kind: BacklightKind) -> u32 {
+        let max = max as f32;
+        let mut value = ((percent as f32 / 100.).powf(self.gamma) * max).round();
+        if kind != BacklightKind::Keyboard {
+            value = value.max((self.min as f32 / 100. * max).ceil());
+        }
+        value.min(max) as u32
+    }
+
+    /// Returns the perceived percentage of `device`
+    pub fn read(&self, device: &dyn BrightnessDevice) -> anyhow::Result<u32> {
+        Ok(self.perceived(device.get()?, device.max()?))
+    }
+}
+
+impl Default for BrightnessCurve {
+    fn default() -> Self {
+        Self { gamma: 1., min: 1 }
+    }
+}
+
+pub struct Brightness {
+    devices: Vec<Box<dyn BrightnessDevice>>,
+    selected: Option<usize>,
+    curve: BrightnessCurve,
+    fade: Duration,
+}
 
 impl Brightness {
-    pub fn handle(operation: BrightnessOp) -> anyhow::Result<()> {
+    /// Drives the device matching `query` (id, name or kind), the first display backlight if
+    /// `None`
+    pub fn new(config: &Config, query: Option<&str>) -> anyhow::Result<Self> {
+        let mut devices = Self::devices(config)?;
+        let find = |devices: &[Box<dyn BrightnessDevice>], query: &str| {
+            devices
+                .iter()
+                .position(|d| d.id() == query || d.name() == query || d.kind().to_string() == query)
+        };
+        let selected = match query {
+            Some(query) => Some(match find(&devices, query) {
+                Some(i) => i,
+                // the monitor may have been plugged in since the last detection
+                None if config.ddc => {
+                    Ddc::refresh()?;
+                    devices = Self::devices(config)?;
+                    find(&devices, query)
+                        .ok_or(anyhow!("Could not find brightness device '{query}'"))?
+                }
+                None => return Err(anyhow!("Could not find brightness device '{query}'")),
+            }),
+            None => devices
+                .iter()
+                .position(|d| d.kind() == BacklightKind::Display)
+                .or((!devices.is_empty()).then_some(0)),
+        };
+        Ok(Self {
+            devices,
+            selected,
+            curve: config.brightness.clone(),
+            fade: Duration::from_millis(config.fade.brightness),
+        })
+    }
+
+    /// Returns the display and keyboard backlights, and external monitors if enabled
+    fn devices(config: &Config) -> anyhow::Result<Vec<Box<dyn BrightnessDevice>>> {
+        let mut devices: Vec<Box<dyn BrightnessDevice>> = Vec::new();
+        for backlight in Backlight::all(&config.sysfs_root, "backlight", "")? {
+            devices.push(Box::new(backlight));
+        }
+        for backlight in Backlight::all(&config.sysfs_root, "leds", "kbd_backlight")? {
+            devices.push(Box::new(backlight));
+        }
+        if config.ddc {
+            for monitor in Ddc::all()? {
+                devices.push(Box::new(monitor));
+            }
+        }
+        Ok(devices)
+    }
+
+    pub fn handle(&self, operation: BrightnessOp) -> anyhow::Result<Option<String>> {
         match operation {
-            BrightnessOp::Up { percent } => Self::up(percent),
-            BrightnessOp::Down { percent } => Self::down(percent),
-            BrightnessOp::Set { percent } => Self::set(percent),
-            BrightnessOp::Update => Self::update(500),
+            BrightnessOp::Up { percent } => self.up(percent),
+            BrightnessOp::Down { percent } => self.down(percent),
+            BrightnessOp::Set { percent } => self.set(percent),
+            BrightnessOp::Update => self.update(500),
+            BrightnessOp::List => return self.list().map(Some),
+        }?;
+        Ok(None)
+    }
+
+    /// Formats the devices one per line, marking the selected one
+    fn list(&self) -> anyhow::Result<String> {
+        let mut lines = Vec::new();
+        for (i, device) in self.devices.iter().enumerate() {
+            let selected = if Some(i) == self.selected { "*" } else { " " };
+            let percent = self
+                .curve
+                .read(device.as_ref())
+                .map_or("?".to_string(), |percent| format!("{percent}%"));
+            lines.push(format!(
+                "{selected} {} ({}, {}) {percent}",
+                device.id(),
+                device.name(),
+                device.kind(),
+            ));
         }
+        Ok(lines.join("\n"))
     }
 
-    fn max() -> anyhow::Result<u32> {
-        let value = utf8_to_u32(Self::exec(&["max"])?.stdout)?;
-        Ok(value)
+    fn device(&self) -> anyhow::Result<&dyn BrightnessDevice> {
+        self.selected
+            .and_then(|i| self.devices.get(i))
+            .map(|device| device.as_ref())
+            .ok_or(anyhow!("Could not find any backlight"))
     }
 
-    fn exec(args: &[impl AsRef<OsStr>]) -> anyhow::Result<Output> {
-        let output = Command::new(PROGRAM).args(args).output()?;
-        Ok(output)
+    /// Publishes and stores the state of every device, leaving out the ones that cannot be read.
+    /// Unless `all`, external monitors other than the selected one keep their stored state, as
+    /// reading them takes a while.
+    fn update_view(&self, all: bool) -> anyhow::Result<()> {
+        let logger = Logger::<Vec<BrightnessState>>::new(JSON_VIEW_NAME);
+        let previous = if all {
+            Vec::new()
+        } else {
+            logger.read().unwrap_or_default()
+        };
+        let states = self
+            .devices
+            .iter()
+            .enumerate()
+            .flat_map(|(i, device)| {
+                match previous.iter().find(|state| state.device == device.id()) {
+                    Some(state)
+                        if device.kind() == BacklightKind::External && Some(i) != self.selected =>
+                    {
+                        Ok(state.clone())
+                    }
+                    _ => BrightnessState::from_device(device.as_ref(), &self.curve),
+                }
+            })
+            .collect::<Vec<BrightnessState>>();
+        logger.overwrite(&states)?;
+        logger.send(&states)
+    }
+
+    /// Sets the brightness to what `f` returns from the current perceived percentage, fading if
+    /// configured.
+    /// Only notifies once the brightness reached its target.
+    fn change(&self, f: impl FnOnce(u32) -> u32) -> anyhow::Result<()> {
+        if self.fade_to(f)? {
+            self.update(0)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Sets the brightness like `set` without notifying, for changes the user did not ask for
+    pub fn set_quietly(&self, percent: u32) -> anyhow::Result<()> {
+        if self.fade_to(|_| percent)? {
+            self.update_view(false)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Fades to what `f` returns, returning `false` when a running fade took the target over
+    fn fade_to(&self, f: impl FnOnce(u32) -> u32) -> anyhow::Result<bool> {
+        let device = self.device()?;
+        let (current, max) = (device.get()?, device.max()?);
+        let percent = self.curve.perceived(current, max);
+        Fade::run(
+            &device.id(),
+            self.fade,
+            percent,
+            |percent| f(percent).min(100),
+            |target| device.set(self.value(device.kind(), current, max, percent, target)),
+        )
+    }
+
+    /// Returns the raw value to give a device of `kind` to go from `percent` (`current` raw out
+    /// of `max`) to `target`
+    fn value(&self, kind: BacklightKind, current: u32, max: u32, percent: u32, target: u32) -> u32 {
+        let value = self.curve.raw(target, max, kind);
+        // steps smaller than a raw unit would otherwise get stuck
+        if value == current && target > percent {
+            (current + 1).min(max)
+        } else if value == current && target < percent {
+            current.saturating_sub(1).max(self.curve.raw(0, max, kind))
+        } else {
+            value
+        }
     }
 }
 
 impl ScaledComponent<BrightnessState> for Brightness {
-    fn get() -> anyhow::Result<u32> {
-        let value = utf8_to_u32(Self::exec(&["get"])?.stdout)?;
-        let percent = value as f32 * 100. / Self::max()? as f32;
-        Ok(percent.round() as u32)
+    fn get(&self) -> anyhow::Result<u32> {
+        self.curve.read(self.device()?)
     }
 
-    fn set(percent: u32) -> anyhow::Result<()> {
-        Self::exec(&["set", &format!("{percent}%")])?;
-        Self::update(0)
+    fn set(&self, percent: u32) -> anyhow::Result<()> {
+        self.change(|_| percent)
     }
 
-    fn up(percent: u32) -> anyhow::Result<()> {
-        Self::exec(&["set", &format!("+{percent}%")])?;
-        Self::update(0)
+    fn up(&self, percent: u32) -> anyhow::Result<()> {
+        self.change(|current| current + percent)
     }
 
-    fn down(percent: u32) -> anyhow::Result<()> {
-        Self::exec(&["set", &format!("{percent}%-")])?;
-        Self::update(0)
+    fn down(&self, percent: u32) -> anyhow::Result<()> {
+        self.change(|current| current.saturating_sub(percent))
     }
 
-    fn get_state() -> anyhow::Result<BrightnessState> {
-        Ok(BrightnessState::new(Self::get()?))
+    fn update(&self, delay: u64) -> anyhow::Result<()> {
+        if delay != 0 {
+            std::thread::sleep(Duration::from_millis(delay));
+        }
+        self.get_state()?.notify()?;
+        self.update_view(false)
+    }
+
+    fn init_view(&self) -> anyhow::Result<()> {
+        self.update_view(true)
+    }
+
+    fn get_state(&self) -> anyhow::Result<BrightnessState> {
+        BrightnessState::from_device(self.device()?, &self.curve)
     }
 }
 
-#[derive(Copy, Clone, Subcommand)]
+#[derive(Copy, Clone, Subcommand, Serialize, Deserialize)]
 pub enum BrightnessOp {
     /// Increase by percentage
     Up {
@@ -79,16 +327,33 @@ pub enum BrightnessOp {
     },
     /// Update status and notify
     Update,
+    /// List brightness devices
+    List,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Clone)]
 pub struct BrightnessState {
+    device: String,
+    name: String,
+    kind: BacklightKind,
     value: u32,
     icon: String,
 }
 
 impl BrightnessState {
-    pub fn new(value: u32) -> Self {
+    pub fn from_device(
+        device: &dyn BrightnessDevice,
+        curve: &BrightnessCurve,
+    ) -> anyhow::Result<Self> {
+        Ok(Self::new(
+            device.id(),
+            device.name(),
+            device.kind(),
+            curve.read(device)?,
+        ))
+    }
+
+    pub fn new(device: String, name: String, kind: BacklightKind, value: u32) -> Self {
         let icon = if value >= 89 {
             " "
         } else if value >= 78 {
@@ -109,7 +374,13 @@ impl BrightnessState {
             " "
         }
         .to_string();
-        Self { value, icon }
+        Self {
+            device,
+            name,
+            kind,
+            value,
+            icon,
+        }
     }
 }
 
@@ -119,11 +390,85 @@ impl NotifiableState for BrightnessState {
     }
 
     fn notify(&self) -> anyhow::Result<()> {
+        let summary = match self.kind {
+            BacklightKind::Display => "Brightness",
+            BacklightKind::Keyboard => "Keyboard backlight",
+            BacklightKind::External => &self.name,
+        };
         Notifier::new("brightness").send(
-            "Brightness",
+            summary,
             &format!("Set to {}%", self.value),
             None,
             Some(self.value),
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn validate_rejects_non_positive_gamma() {
+        for gamma in [0., -1., f32::NAN, f32::INFINITY] {
+            let curve = BrightnessCurve { gamma, min: 1 };
+            assert!(curve.validate().is_err(), "gamma {gamma} was accepted");
+        }
+        assert!(BrightnessCurve::default().validate().is_ok());
+        assert!(BrightnessCurve { gamma: 2.2, min: 1 }.validate().is_ok());
+    }
+
+    #[test]
+    fn raw_and_perceived_round_trip() {
+        for (gamma, max) in [(1., 255), (1., 1000), (2.2, 96000)] {
+            let curve = BrightnessCurve { gamma, min: 0 };
+            for percent in 0..=100 {
+                let raw = curve.raw(percent, max, BacklightKind::Display);
+                assert_eq!(
+                    curve.perceived(raw, max),
+                    percent,
+                    "gamma {gamma}, max {max}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn gamma_spreads_the_low_end() {
+        let curve = BrightnessCurve { gamma: 2., min: 0 };
+        assert_eq!(curve.raw(10, 1000, BacklightKind::Display), 10);
+        assert_eq!(curve.raw(50, 1000, BacklightKind::Display), 250);
+        assert_eq!(curve.perceived(10, 1000), 10);
+        assert_eq!(curve.perceived(250, 1000), 50);
+        // a linear curve gives the low end ten times the raw range
+        let linear = BrightnessCurve { gamma: 1., min: 0 };
+        assert_eq!(linear.raw(10, 1000, BacklightKind::Display), 100);
+    }
+
+    #[test]
+    fn min_floors_displays_but_not_keyboards() {
+        let curve = BrightnessCurve { gamma: 1., min: 5 };
+        assert_eq!(curve.raw(0, 1000, BacklightKind::Display), 50);
+        assert_eq!(curve.raw(0, 1000, BacklightKind::External), 50);
+        assert_eq!(curve.raw(2, 1000, BacklightKind::Display), 50);
+        assert_eq!(curve.raw(10, 1000, BacklightKind::Display), 100);
+        assert_eq!(curve.raw(0, 2, BacklightKind::Keyboard), 0);
+        assert_eq!(curve.raw(0, 1000, BacklightKind::Keyboard), 0);
+        // rounded up so that tiny ranges still light up
+        assert_eq!(curve.raw(0, 10, BacklightKind::Display), 1);
+    }
+
+    #[test]
+    fn raw_is_clamped_at_max() {
+        let curve = BrightnessCurve { gamma: 2.2, min: 1 };
+        assert_eq!(curve.raw(100, 1000, BacklightKind::Display), 1000);
+        assert_eq!(curve.raw(150, 1000, BacklightKind::Display), 1000);
+        let floor = BrightnessCurve {
+            gamma: 1.,
+            min: 150,
+        };
+        assert_eq!(floor.raw(0, 1000, BacklightKind::Display), 1000);
+        assert_eq!(curve.perceived(1000, 1000), 100);
+        assert_eq!(curve.perceived(0, 0), 0);
+    }
+}
@@ -0,0 +1,130 @@
+use std::ffi::OsStr;
+use std::process::{Command, Output};
+use std::sync::Mutex;
+
+use anyhow::anyhow;
+
+use super::{BacklightKind, BrightnessDevice};
+
+const PROGRAM: &str = "ddcutil";
+/// VCP feature code of the luminance
+const BRIGHTNESS_FEATURE: &str = "10";
+
+/// Monitors found by the last detection, which takes seconds
+static DETECTED: Mutex<Option<Vec<Ddc>>> = Mutex::new(None);
+
+/// An external monitor driven over DDC/CI through `ddcutil`
+#[derive(Clone)]
+pub struct Ddc {
+    display: u32,
+    model: String,
+    /// Maximum brightness, read once at detection as every query takes a while
+    max: u32,
+}
+
+impl Ddc {
+    /// Returns the monitors answering to DDC/CI, detected once and then remembered
+    pub fn all() -> anyhow::Result<Vec<Self>> {
+        let mut detected = DETECTED.lock().unwrap();
+        if detected.is_none() {
+            *detected = Some(Self::detect()?);
+        }
+        Ok(detected.clone().unwrap_or_default())
+    }
+
+    /// Detects the monitors again, for when some were plugged in or out
+    pub fn refresh() -> anyhow::Result<()> {
+        *DETECTED.lock().unwrap() = Some(Self::detect()?);
+        Ok(())
+    }
+
+    /// Returns the monitors answering to DDC/CI, leaving out the ones whose brightness cannot be
+    /// read
+    fn detect() -> anyhow::Result<Vec<Self>> {
+        let output = String::from_utf8(Self::exec(&["detect", "--brief"])?.stdout)?;
+        let mut monitors: Vec<Self> = Vec::new();
+        for line in output.lines() {
+            // monitors which do not support DDC/CI are listed as `Invalid display`
+            if let Some(display) = line.strip_prefix("Display ") {
+                monitors.push(Self {
+                    display: display.trim().parse()?,
+                    model: format!("Display {}", display.trim()),
+                    max: 0,
+                });
+            } else if let Some(monitor) = line.trim().strip_prefix("Monitor:") {
+                // `Monitor: DEL:DELL U2415:SERIAL`
+                if let (Some(last), Some(model)) = (monitors.last_mut(), monitor.split(':').nth(1))
+                {
+                    last.model = model.trim().to_string();
+                }
+            }
+        }
+        monitors.retain_mut(|monitor| match monitor.values() {
+            Ok((_, max)) => {
+                monitor.max = max;
+                true
+            }
+            Err(e) => {
+                eprintln!("Could not read the brightness of {}: {e}", monitor.model);
+                false
+            }
+        });
+        Ok(monitors)
+    }
+
+    /// Returns the current and maximum values of the brightness
+    fn values(&self) -> anyhow::Result<(u32, u32)> {
+        let output = self.exec_display(&["--brief", "getvcp", BRIGHTNESS_FEATURE])?;
+        // Output looks like `VCP 10 C 50 100`
+        let string = String::from_utf8(output.stdout)?;
+        let fields = string.split_whitespace().collect::<Vec<&str>>();
+        match fields[..] {
+            ["VCP", _, "C", current, max] => Ok((current.parse()?, max.parse()?)),
+            _ => Err(anyhow!("Invalid ddcutil output: '{}'", string.trim())),
+        }
+    }
+
+    fn exec_display(&self, args: &[&str]) -> anyhow::Result<Output> {
+        let display = self.display.to_string();
+        let output = Self::exec(&[&["--display", &display], args].concat())?;
+        if output.status.success() {
+            Ok(output)
+        } else {
+            Err(anyhow!(String::from_utf8(output.stderr)?
+                .trim()
+                .to_string()))
+        }
+    }
+
+    fn exec(args: &[impl AsRef<OsStr>]) -> anyhow::Result<Output> {
+        let output = Command::new(PROGRAM).args(args).output()?;
+        Ok(output)
+    }
+}
+
+impl BrightnessDevice for Ddc {
+    fn id(&self) -> String {
+        format!("ddc/{}", self.display)
+    }
+
+    fn name(&self) -> String {
+        self.model.clone()
+    }
+
+    fn kind(&self) -> BacklightKind {
+        BacklightKind::External
+    }
+
+    fn get(&self) -> anyhow::Result<u32> {
+        self.values().map(|(current, _)| current)
+    }
+
+    fn max(&self) -> anyhow::Result<u32> {
+        Ok(self.max)
+    }
+
+    fn set(&self, value: u32) -> anyhow::Result<()> {
+        self.exec_display(&["setvcp", BRIGHTNESS_FEATURE, &value.to_string()])?;
+        Ok(())
+    }
+}
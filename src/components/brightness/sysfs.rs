@@ -0,0 +1,139 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::anyhow;
+
+use crate::components::system::System;
+
+use super::{BacklightKind, BrightnessDevice};
+
+/// A backlight exposed by the kernel under `/sys/class/<subsystem>/<name>`
+pub struct Backlight {
+    pub subsystem: String,
+    pub name: String,
+    path: PathBuf,
+}
+
+impl Backlight {
+    /// Returns the backlights of `subsystem` under the sysfs mounted at `root` whose name
+    /// contains `filter`, sorted by name
+    pub fn all(root: &Path, subsystem: &str, filter: &str) -> anyhow::Result<Vec<Self>> {
+        let dir = root.join("class").join(subsystem);
+        if !dir.exists() {
+            return Ok(Vec::new());
+        }
+        let mut backlights = std::fs::read_dir(dir)?
+            .map(|entry| {
+                let entry = entry?;
+                Ok(Self {
+                    subsystem: subsystem.to_string(),
+                    name: entry.file_name().to_string_lossy().to_string(),
+                    path: entry.path(),
+                })
+            })
+            .filter(|backlight: &anyhow::Result<Self>| {
+                backlight.as_ref().map_or(true, |b| b.name.contains(filter))
+            })
+            .collect::<anyhow::Result<Vec<Self>>>()?;
+        backlights.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(backlights)
+    }
+
+    fn read(&self, file: &str) -> anyhow::Result<u32> {
+        let path = self.path.join(file);
+        std::fs::read_to_string(&path)?
+            .trim()
+            .parse()
+            .map_err(|e| anyhow!("Invalid value in {}: {e}", path.display()))
+    }
+}
+
+impl BrightnessDevice for Backlight {
+    fn id(&self) -> String {
+        format!("{}/{}", self.subsystem, self.name)
+    }
+
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    fn kind(&self) -> BacklightKind {
+        match self.subsystem.as_str() {
+            "leds" => BacklightKind::Keyboard,
+            _ => BacklightKind::Display,
+        }
+    }
+
+    fn get(&self) -> anyhow::Result<u32> {
+        self.read("brightness")
+    }
+
+    fn max(&self) -> anyhow::Result<u32> {
+        self.read("max_brightness")
+    }
+
+    /// Writes through logind, which does not require write access to sysfs
+    fn set(&self, value: u32) -> anyhow::Result<()> {
+        System::call_session("SetBrightness", &(&self.subsystem, &self.name, value))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fake_sysfs::FakeSysfs;
+
+    /// Builds a fake sysfs with display backlights and leds
+    fn with_backlights(name: &str) -> FakeSysfs {
+        let mut sysfs = FakeSysfs::new(&format!("sysfs-{name}"));
+        for (device, brightness, max) in [
+            ("backlight/intel_backlight", 400, 1000),
+            ("backlight/acpi_video0", 5, 10),
+            ("leds/tpacpi::kbd_backlight", 1, 2),
+            ("leds/input3::capslock", 0, 1),
+        ] {
+            sysfs = sysfs
+                .file(&format!("class/{device}/brightness"), brightness)
+                .file(&format!("class/{device}/max_brightness"), max);
+        }
+        sysfs
+    }
+
+    #[test]
+    fn all_lists_backlights_sorted_by_name() {
+        let sysfs = with_backlights("sorted");
+        let backlights = Backlight::all(sysfs.root(), "backlight", "").unwrap();
+        let ids = backlights.iter().map(|b| b.id()).collect::<Vec<String>>();
+        assert_eq!(ids, ["backlight/acpi_video0", "backlight/intel_backlight"]);
+        assert!(backlights
+            .iter()
+            .all(|b| b.kind() == BacklightKind::Display));
+    }
+
+    #[test]
+    fn all_filters_keyboard_backlights() {
+        let sysfs = with_backlights("filter");
+        let leds = Backlight::all(sysfs.root(), "leds", "kbd_backlight").unwrap();
+        assert_eq!(leds.len(), 1);
+        assert_eq!(leds[0].name(), "tpacpi::kbd_backlight");
+        assert_eq!(leds[0].kind(), BacklightKind::Keyboard);
+    }
+
+    #[test]
+    fn all_ignores_missing_subsystems() {
+        let sysfs = with_backlights("missing");
+        assert!(Backlight::all(sysfs.root(), "nothing", "")
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn reads_brightness_and_max() {
+        let sysfs = with_backlights("read");
+        let backlight = Backlight::all(sysfs.root(), "backlight", "intel")
+            .unwrap()
+            .remove(0);
+        assert_eq!(backlight.get().unwrap(), 400);
+        assert_eq!(backlight.max().unwrap(), 1000);
+    }
+}
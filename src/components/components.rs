@@ -5,22 +5,22 @@ use serde::{Deserialize, Serialize};
 use crate::logger::Logger;
 
 pub trait ScaledComponent<T: NotifiableState> {
-    fn get() -> anyhow::Result<u32>;
-    fn set(percent: u32) -> anyhow::Result<()>;
-    fn up(percent: u32) -> anyhow::Result<()>;
-    fn down(percent: u32) -> anyhow::Result<()>;
-    fn get_state() -> anyhow::Result<T>;
+    fn get(&self) -> anyhow::Result<u32>;
+    fn set(&self, percent: u32) -> anyhow::Result<()>;
+    fn up(&self, percent: u32) -> anyhow::Result<()>;
+    fn down(&self, percent: u32) -> anyhow::Result<()>;
+    fn get_state(&self) -> anyhow::Result<T>;
 
-    fn update(delay: u64) -> anyhow::Result<()> {
+    fn update(&self, delay: u64) -> anyhow::Result<()> {
         if delay != 0 {
             std::thread::sleep(Duration::from_millis(delay));
         }
-        let state = Self::get_state()?;
+        let state = self.get_state()?;
         state.notify()?;
         state.update_view()
     }
-    fn init_view() -> anyhow::Result<()> {
-        Self::get_state()?.update_view()
+    fn init_view(&self) -> anyhow::Result<()> {
+        self.get_state()?.update_view()
     }
 }
 
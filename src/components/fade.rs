@@ -0,0 +1,91 @@
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+/// Targets of the ongoing transitions, by component
+static TARGETS: Mutex<BTreeMap<String, u32>> = Mutex::new(BTreeMap::new());
+const FRAME: Duration = Duration::from_millis(16);
+
+/// Durations of the transitions in milliseconds, 0 changes instantly
+#[derive(Deserialize, Serialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct FadeConfig {
+    pub brightness: u64,
+    pub volume: u64,
+}
+
+pub struct Fade;
+
+impl Fade {
+    /// Moves `id` from `current` to what `f` returns over `duration`, calling `apply` with the
+    /// intermediate values.
+    /// If `id` is already fading, `f` is applied to its target instead and the running transition
+    /// heads to the new one, in which case this returns `false` without waiting.
+    pub fn run(
+        id: &str,
+        duration: Duration,
+        current: u32,
+        f: impl FnOnce(u32) -> u32,
+        apply: impl Fn(u32) -> anyhow::Result<()>,
+    ) -> anyhow::Result<bool> {
+        let target = {
+            let mut targets = TARGETS.lock().unwrap();
+            if let Some(target) = targets.get_mut(id) {
+                *target = f(*target);
+                return Ok(false);
+            }
+            let target = f(current);
+            if duration.is_zero() {
+                drop(targets);
+                apply(target)?;
+                return Ok(true);
+            }
+            targets.insert(id.to_string(), target);
+            target
+        };
+
+        let result = Self::animate(id, duration, current, target, apply);
+        if result.is_err() {
+            TARGETS.lock().unwrap().remove(id);
+        }
+        result.map(|_| true)
+    }
+
+    /// Whether `id` is fading
+    pub fn running(id: &str) -> bool {
+        TARGETS.lock().unwrap().contains_key(id)
+    }
+
+    fn animate(
+        id: &str,
+        duration: Duration,
+        current: u32,
+        target: u32,
+        apply: impl Fn(u32) -> anyhow::Result<()>,
+    ) -> anyhow::Result<()> {
+        let (mut from, mut to, mut last) = (current, target, current);
+        let mut start = Instant::now();
+        loop {
+            let target = TARGETS.lock().unwrap().get(id).copied().unwrap_or(to);
+            if target != to {
+                (from, to, start) = (last, target, Instant::now());
+            }
+            let progress = (start.elapsed().as_secs_f32() / duration.as_secs_f32()).min(1.);
+            let value = (from as f32 + (to as f32 - from as f32) * progress).round() as u32;
+            if value != last || progress >= 1. {
+                apply(value)?;
+                last = value;
+            }
+            if progress >= 1. {
+                let mut targets = TARGETS.lock().unwrap();
+                if targets.get(id) == Some(&to) {
+                    targets.remove(id);
+                    return Ok(());
+                }
+            }
+            std::thread::sleep(FRAME);
+        }
+    }
+}
@@ -0,0 +1,183 @@
+use anyhow::anyhow;
+use serde::{Deserialize, Serialize};
+use wayland_client::globals::{registry_queue_init, GlobalListContents};
+use wayland_client::protocol::{
+    wl_registry::{self, WlRegistry},
+    wl_seat::WlSeat,
+};
+use wayland_client::{delegate_noop, Connection, Dispatch, QueueHandle};
+use wayland_protocols::ext::idle_notify::v1::client::{
+    ext_idle_notification_v1::{self, ExtIdleNotificationV1},
+    ext_idle_notifier_v1::ExtIdleNotifierV1,
+};
+
+use crate::config::Config;
+
+use super::brightness::Brightness;
+use super::components::ScaledComponent;
+use super::system::{inhibit::Inhibitors, System};
+
+/// Seconds of inactivity before each stage, unset stages never happen
+#[derive(Deserialize, Serialize, Clone, Debug)]
+#[serde(default)]
+pub struct IdleConfig {
+    pub dim: Option<u32>,
+    /// Perceived percentage the display is dimmed to
+    pub dim_brightness: u32,
+    pub lock: Option<u32>,
+    pub screen_off: Option<u32>,
+    pub suspend: Option<u32>,
+}
+
+impl Default for IdleConfig {
+    fn default() -> Self {
+        Self {
+            dim: None,
+            dim_brightness: 10,
+            lock: None,
+            screen_off: None,
+            suspend: None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Stage {
+    Dim,
+    Lock,
+    ScreenOff,
+    Suspend,
+}
+
+/// Stages that became idle (`true`) or resumed (`false`) since the last dispatch
+#[derive(Default)]
+struct Events(Vec<(Stage, bool)>);
+
+impl Dispatch<ExtIdleNotificationV1, Stage> for Events {
+    fn event(
+        events: &mut Self,
+        _: &ExtIdleNotificationV1,
+        event: ext_idle_notification_v1::Event,
+        stage: &Stage,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        match event {
+            ext_idle_notification_v1::Event::Idled => events.0.push((*stage, true)),
+            ext_idle_notification_v1::Event::Resumed => events.0.push((*stage, false)),
+            _ => (),
+        }
+    }
+}
+
+impl Dispatch<WlRegistry, GlobalListContents> for Events {
+    fn event(
+        _: &mut Self,
+        _: &WlRegistry,
+        _: wl_registry::Event,
+        _: &GlobalListContents,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+delegate_noop!(Events: ignore WlSeat);
+delegate_noop!(Events: ExtIdleNotifierV1);
+
+/// Dims, locks, turns off the screen and suspends when the user is inactive
+pub struct Idle<'a> {
+    config: &'a Config,
+    /// Brightness before dimming, to restore on activity
+    dimmed: Option<u32>,
+    screen_off: bool,
+}
+
+impl<'a> Idle<'a> {
+    /// Follows the inactivity of the user through the compositor
+    pub fn listen(config: &'a Config) -> anyhow::Result<()> {
+        let stages = [
+            (Stage::Dim, config.idle.dim),
+            (Stage::Lock, config.idle.lock),
+            (Stage::ScreenOff, config.idle.screen_off),
+            (Stage::Suspend, config.idle.suspend),
+        ];
+        if stages.iter().all(|(_, timeout)| timeout.is_none()) {
+            return Ok(());
+        }
+
+        let connection = Connection::connect_to_env()?;
+        let (globals, mut queue) = registry_queue_init::<Events>(&connection)?;
+        let handle = queue.handle();
+        let seat: WlSeat = globals.bind(&handle, 1..=1, ())?;
+        let notifier: ExtIdleNotifierV1 = globals
+            .bind(&handle, 1..=1, ())
+            .map_err(|e| anyhow!("The compositor does not support idle notifications: {e}"))?;
+        let _notifications = stages
+            .into_iter()
+            .flat_map(|(stage, timeout)| {
+                timeout.map(|timeout| {
+                    notifier.get_idle_notification(timeout * 1000, &seat, &handle, stage)
+                })
+            })
+            .collect::<Vec<ExtIdleNotificationV1>>();
+
+        let mut idle = Self {
+            config,
+            dimmed: None,
+            screen_off: false,
+        };
+        let mut events = Events::default();
+        loop {
+            queue.blocking_dispatch(&mut events)?;
+            for (stage, idled) in events.0.drain(..) {
+                if let Err(e) = idle.handle(stage, idled) {
+                    eprintln!("Could not handle idle stage {stage:?}: {e}");
+                }
+            }
+        }
+    }
+
+    fn handle(&mut self, stage: Stage, idled: bool) -> anyhow::Result<()> {
+        if !idled {
+            return self.resume(stage);
+        }
+        if Inhibitors::inhibited() {
+            return Ok(());
+        }
+        match stage {
+            Stage::Dim => {
+                let brightness = Brightness::new(self.config, None)?;
+                let current = brightness.get()?;
+                // a display already darker is left alone, and so is not restored later
+                if current <= self.config.idle.dim_brightness {
+                    return Ok(());
+                }
+                self.dimmed = Some(current);
+                brightness.set_quietly(self.config.idle.dim_brightness)
+            }
+            Stage::Lock => System::lock(),
+            Stage::ScreenOff => {
+                self.config.compositor_type.set_screen(false)?;
+                self.screen_off = true;
+                Ok(())
+            }
+            Stage::Suspend => System::suspend(),
+        }
+    }
+
+    /// Undoes what `stage` did once the user is active again
+    fn resume(&mut self, stage: Stage) -> anyhow::Result<()> {
+        match stage {
+            Stage::Dim => match self.dimmed.take() {
+                Some(percent) => Brightness::new(self.config, None)?.set_quietly(percent),
+                None => Ok(()),
+            },
+            Stage::ScreenOff if self.screen_off => {
+                self.screen_off = false;
+                self.config.compositor_type.set_screen(true)
+            }
+            _ => Ok(()),
+        }
+    }
+}
@@ -114,7 +114,7 @@ impl Serialize for Layout {
     }
 }
 
-#[derive(Clone, clap::Subcommand)]
+#[derive(Clone, clap::Subcommand, Serialize, Deserialize)]
 pub enum LayoutOp {
     Set { layout: String },
     Switch,
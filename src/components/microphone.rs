@@ -1,110 +1,138 @@
-use std::{
-    ffi::OsStr,
-    process::{Command, Output},
-};
-
 use clap::Subcommand;
-use regex::Regex;
 use serde::{Deserialize, Serialize};
 
+use crate::config::Config;
+use crate::logger::Logger;
 use crate::notifier::Notifier;
 
+use super::audio::{AudioBackend, AudioDevice, AudioEvent, AudioLevel, DeviceKind};
 use super::components::{NotifiableState, ScaledComponent};
 
-pub struct Microphone;
-const PROGRAM: &str = "amixer";
-const MUTE_COMMAND: &[&str] = &["-D", "pulse", "set", "Capture", "1+", "toggle"];
+pub struct Microphone {
+    backend: Box<dyn AudioBackend>,
+}
 const JSON_VIEW_NAME: &str = "microphone-json";
+const DEVICES_JSON_VIEW_NAME: &str = "microphone-devices-json";
+const MAX_VOLUME: u32 = 100;
 
 impl Microphone {
-    pub fn working() -> anyhow::Result<bool> {
-        Ok(Self::get_data()?.stderr.is_empty())
+    pub fn new(config: &Config) -> anyhow::Result<Self> {
+        Ok(Self {
+            backend: config.audio_backend.backend()?,
+        })
     }
 
-    fn get_data() -> anyhow::Result<Output> {
-        Self::exec(&["get", "Capture"])
+    /// Publishes the state of the microphone every time the audio server reports a change
+    pub fn listen(config: &Config) -> anyhow::Result<()> {
+        let microphone = Self::new(config)?;
+        // catches up with what happened while the audio server could not be reached
+        microphone.init_view()?;
+        let mut current = microphone.get_state()?;
+        let mut devices = microphone.devices()?;
+        config.audio_backend.backend()?.subscribe(&mut |event| {
+            if event == AudioEvent::Source {
+                let state = microphone.get_state()?;
+                if state != current {
+                    state.notify()?;
+                    state.update_view()?;
+                    current = state;
+                }
+                let new_devices = microphone.devices()?;
+                if new_devices != devices {
+                    Logger::new(DEVICES_JSON_VIEW_NAME).send(&new_devices)?;
+                    devices = new_devices;
+                }
+            }
+            Ok(())
+        })
     }
 
-    fn muted() -> anyhow::Result<bool> {
-        Self::get_state().map(|v| v.muted)
+    fn mute(&self) -> anyhow::Result<()> {
+        if let Some(level) = self.backend.get(DeviceKind::Source)? {
+            self.backend.set_mute(DeviceKind::Source, !level.muted)?;
+        }
+        self.update(0)
     }
 
-    fn exec(args: &[impl AsRef<OsStr>]) -> anyhow::Result<Output> {
-        let output = Command::new(PROGRAM).args(args).output()?;
-        Ok(output)
+    pub fn handle(&self, operation: MicrophoneOp) -> anyhow::Result<Option<String>> {
+        match operation {
+            MicrophoneOp::Mute => self.mute(),
+            MicrophoneOp::Up { percent } => self.up(percent),
+            MicrophoneOp::Down { percent } => self.down(percent),
+            MicrophoneOp::Set { percent } => self.set(percent),
+            MicrophoneOp::Update => self.update(500),
+            MicrophoneOp::Devices => return Ok(Some(AudioDevice::list(&self.devices()?))),
+            MicrophoneOp::Select { device } => self.select(&device),
+        }?;
+        Ok(None)
     }
 
-    fn mute() -> anyhow::Result<()> {
-        Self::exec(MUTE_COMMAND)?;
-        Self::update(0)
+    fn devices(&self) -> anyhow::Result<Vec<AudioDevice>> {
+        self.backend.devices(DeviceKind::Source)
     }
 
-    pub fn handle(operation: MicrophoneOp) -> anyhow::Result<()> {
-        match operation {
-            MicrophoneOp::Mute => Self::mute(),
-            MicrophoneOp::Up { percent } => Self::up(percent),
-            MicrophoneOp::Down { percent } => Self::down(percent),
-            MicrophoneOp::Set { percent } => Self::set(percent),
-            MicrophoneOp::Update => Self::update(500),
-        }
+    /// Makes the input matching `query` the default one
+    fn select(&self, query: &str) -> anyhow::Result<()> {
+        let device = AudioDevice::find(self.devices()?, query)?;
+        self.backend.set_default(DeviceKind::Source, &device)?;
+        Notifier::new("microphone").send(
+            "Microphone",
+            &format!("Input set to {}", device.description),
+            None,
+            None,
+        )?;
+        self.get_state()?.update_view()?;
+        Logger::new(DEVICES_JSON_VIEW_NAME).send(&self.devices()?)
     }
 
-    pub(crate) fn init_view() -> anyhow::Result<()> {
-        let state = Self::get_state()?;
-        state.update_view()
+    /// Sets the volume to what `f` returns from the current one, unless the input is muted
+    fn change(&self, f: impl FnOnce(u32) -> u32) -> anyhow::Result<()> {
+        if let Some(AudioLevel {
+            volume,
+            muted: false,
+        }) = self.backend.get(DeviceKind::Source)?
+        {
+            self.backend
+                .set_volume(DeviceKind::Source, f(volume).min(MAX_VOLUME))?;
+        }
+        self.update(0)
     }
 }
 
 impl ScaledComponent<MicrophoneState> for Microphone {
-    fn get() -> anyhow::Result<u32> {
-        if Self::working()? {
-            Self::get_state().map(|v| v.value)
-        } else {
-            Ok(0)
-        }
+    fn get(&self) -> anyhow::Result<u32> {
+        Ok(self
+            .backend
+            .get(DeviceKind::Source)?
+            .map_or(0, |level| level.volume))
     }
 
-    fn set(percent: u32) -> anyhow::Result<()> {
-        if !Self::muted()? {
-            Self::exec(&["sset", "Capture", &format!("{percent}%")])?;
-        }
-        Self::update(0)
+    fn set(&self, percent: u32) -> anyhow::Result<()> {
+        self.change(|_| percent)
     }
 
-    fn up(percent: u32) -> anyhow::Result<()> {
-        if !Self::muted()? {
-            Self::exec(&["sset", "Capture", &format!("{percent}%+")])?;
-        }
-        Self::update(0)
+    fn up(&self, percent: u32) -> anyhow::Result<()> {
+        self.change(|volume| volume + percent)
     }
 
-    fn down(percent: u32) -> anyhow::Result<()> {
-        if !Self::muted()? {
-            Self::exec(&["sset", "Capture", &format!("{percent}%-")])?;
-        }
-        Self::update(0)
-    }
-
-    fn get_state() -> anyhow::Result<MicrophoneState> {
-        if Self::working()? {
-            let reg = Regex::new(r"(?m)^.* \[(?<percent>\d+)%\] \[(?<mute>.*)\]$")?;
-            let output = String::from_utf8(Self::get_data()?.stdout)?;
-            let data = reg
-                .captures(&output)
-                .ok_or_else(|| anyhow::anyhow!("invalid output command"))?;
-
-            Ok(MicrophoneState::new(
-                true,
-                data["mute"].trim() != "on",
-                data["percent"].trim().parse::<u32>()?,
-            ))
-        } else {
-            Ok(MicrophoneState::new(false, false, 0))
-        }
+    fn down(&self, percent: u32) -> anyhow::Result<()> {
+        self.change(|volume| volume.saturating_sub(percent))
+    }
+
+    fn init_view(&self) -> anyhow::Result<()> {
+        self.get_state()?.update_view()?;
+        Logger::new(DEVICES_JSON_VIEW_NAME).send(&self.devices()?)
+    }
+
+    fn get_state(&self) -> anyhow::Result<MicrophoneState> {
+        Ok(match self.backend.get(DeviceKind::Source)? {
+            Some(level) => MicrophoneState::new(true, level.muted, level.volume),
+            None => MicrophoneState::new(false, false, 0),
+        })
     }
 }
 
-#[derive(Copy, Clone, Subcommand)]
+#[derive(Clone, Subcommand, Serialize, Deserialize)]
 pub enum MicrophoneOp {
     /// Increase by percentage
     Up {
@@ -125,9 +153,16 @@ pub enum MicrophoneOp {
     Mute,
     /// Update status and notify
     Update,
+    /// List input devices
+    Devices,
+    /// Set the default input device
+    Select {
+        /// Id, name or description of the device
+        device: String,
+    },
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
 pub struct MicrophoneState {
     value: u32,
     working: bool,
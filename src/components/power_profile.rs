@@ -0,0 +1,224 @@
+use std::collections::HashMap;
+
+use anyhow::anyhow;
+use clap::{Subcommand, ValueEnum};
+use serde::{Deserialize, Serialize};
+use zbus::blocking::fdo::{DBusProxy, PropertiesProxy};
+use zbus::blocking::{Connection, MessageIterator};
+use zbus::names::{BusName, InterfaceName};
+use zbus::zvariant::{OwnedValue, Value};
+use zbus::{MatchRule, MessageType};
+
+use crate::notifier::Notifier;
+
+use super::components::NotifiableState;
+
+const DESTINATION: &str = "net.hadess.PowerProfiles";
+const PATH: &str = "/net/hadess/PowerProfiles";
+const JSON_VIEW_NAME: &str = "power-profile-json";
+
+/// Client of power-profiles-daemon
+pub struct PowerProfile {
+    connection: Connection,
+}
+
+impl PowerProfile {
+    pub fn new() -> anyhow::Result<Self> {
+        Ok(Self {
+            connection: Connection::system()?,
+        })
+    }
+
+    /// Publishes the active profile every time it changes, stopping right away when
+    /// power-profiles-daemon is not installed
+    pub fn listen() -> anyhow::Result<()> {
+        let power_profile = Self::new()?;
+        let rule = MatchRule::builder()
+            .msg_type(MessageType::Signal)
+            .sender(DESTINATION)?
+            .path(PATH)?
+            .member("PropertiesChanged")?
+            .build();
+        let mut current = match power_profile.get_state() {
+            Ok(state) => state,
+            // power-profiles-daemon is optional
+            Err(_) if !power_profile.running()? => return Ok(()),
+            Err(e) => return Err(e),
+        };
+        for signal in MessageIterator::for_match_rule(rule, &power_profile.connection, None)? {
+            signal?;
+            let state = power_profile.get_state()?;
+            if state != current {
+                state.update_view()?;
+                current = state;
+            }
+        }
+        Err(anyhow!("Connection to power-profiles-daemon closed"))
+    }
+
+    pub fn handle(&self, operation: PowerProfileOp) -> anyhow::Result<Option<String>> {
+        match operation {
+            PowerProfileOp::Get => return Ok(Some(self.get()?.to_string())),
+            PowerProfileOp::Set { profile } => self.set(profile),
+            PowerProfileOp::Cycle => self.cycle(),
+        }?;
+        Ok(None)
+    }
+
+    /// Whether power-profiles-daemon is on the bus
+    fn running(&self) -> anyhow::Result<bool> {
+        Ok(DBusProxy::new(&self.connection)?.name_has_owner(BusName::try_from(DESTINATION)?)?)
+    }
+
+    pub fn get(&self) -> anyhow::Result<Profile> {
+        let profile = String::try_from(self.property("ActiveProfile")?)?;
+        Profile::from_name(&profile)
+    }
+
+    pub fn set(&self, profile: Profile) -> anyhow::Result<()> {
+        self.proxy()?.set(
+            InterfaceName::try_from(DESTINATION)?,
+            "ActiveProfile",
+            &Value::from(profile.name()),
+        )?;
+        let state = self.get_state()?;
+        state.notify()?;
+        state.update_view()
+    }
+
+    /// Switches to the next profile supported by the computer
+    pub fn cycle(&self) -> anyhow::Result<()> {
+        let profiles = self.profiles()?;
+        let current = self.get()?;
+        let next = profiles
+            .iter()
+            .position(|profile| *profile == current)
+            .map_or(0, |i| (i + 1) % profiles.len());
+        self.set(
+            *profiles
+                .get(next)
+                .ok_or(anyhow!("No power profile available"))?,
+        )
+    }
+
+    pub fn init_view(&self) -> anyhow::Result<()> {
+        self.get_state()?.update_view()
+    }
+
+    fn get_state(&self) -> anyhow::Result<PowerProfileState> {
+        Ok(PowerProfileState::new(self.get()?))
+    }
+
+    /// Profiles supported by the computer, from the most saving
+    fn profiles(&self) -> anyhow::Result<Vec<Profile>> {
+        let profiles = Vec::<HashMap<String, OwnedValue>>::try_from(self.property("Profiles")?)?;
+        let mut profiles = profiles
+            .into_iter()
+            .flat_map(|profile| profile.get("Profile").cloned())
+            .flat_map(String::try_from)
+            .flat_map(|name| Profile::from_name(&name))
+            .collect::<Vec<Profile>>();
+        profiles.sort();
+        Ok(profiles)
+    }
+
+    fn property(&self, name: &str) -> anyhow::Result<OwnedValue> {
+        Ok(self
+            .proxy()?
+            .get(InterfaceName::try_from(DESTINATION)?, name)?)
+    }
+
+    fn proxy(&self) -> anyhow::Result<PropertiesProxy<'_>> {
+        Ok(PropertiesProxy::builder(&self.connection)
+            .destination(DESTINATION)?
+            .path(PATH)?
+            .build()?)
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Debug, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Profile {
+    PowerSaver,
+    Balanced,
+    Performance,
+}
+
+impl Profile {
+    /// Name used by power-profiles-daemon
+    fn name(&self) -> &'static str {
+        match self {
+            Profile::PowerSaver => "power-saver",
+            Profile::Balanced => "balanced",
+            Profile::Performance => "performance",
+        }
+    }
+
+    fn from_name(name: &str) -> anyhow::Result<Self> {
+        match name {
+            "power-saver" => Ok(Profile::PowerSaver),
+            "balanced" => Ok(Profile::Balanced),
+            "performance" => Ok(Profile::Performance),
+            _ => Err(anyhow!("Unknown power profile '{name}'")),
+        }
+    }
+}
+
+impl std::fmt::Display for Profile {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+#[derive(Clone, Subcommand, Serialize, Deserialize)]
+pub enum PowerProfileOp {
+    /// Print the active profile
+    Get,
+    /// Set the active profile
+    Set {
+        #[arg(value_enum)]
+        profile: Profile,
+    },
+    /// Switch to the next profile
+    Cycle,
+}
+
+#[derive(Serialize, Deserialize, PartialEq)]
+pub struct PowerProfileState {
+    profile: Profile,
+    icon: String,
+}
+
+impl PowerProfileState {
+    pub fn new(profile: Profile) -> Self {
+        let icon = match profile {
+            Profile::PowerSaver => "\u{f06c} ",
+            Profile::Balanced => "\u{f24e} ",
+            Profile::Performance => "\u{f0e7} ",
+        }
+        .to_string();
+        Self { profile, icon }
+    }
+}
+
+impl NotifiableState for PowerProfileState {
+    fn json_name(&self) -> &str {
+        JSON_VIEW_NAME
+    }
+
+    fn notify(&self) -> anyhow::Result<()> {
+        Notifier::new("power-profile").send(
+            "Power profile",
+            &format!("Set to {}", self.profile),
+            None,
+            None,
+        )
+    }
+}
+
+/// Switches to power-saver when discharging below `saver_below` percents
+#[derive(Deserialize, Serialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct PowerProfileConfig {
+    pub saver_below: Option<u32>,
+}
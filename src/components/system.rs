@@ -1,26 +1,76 @@
-use clap::{Subcommand, ValueEnum};
-use serde::Serialize;
+pub mod inhibit;
+
+use clap::{Args, Subcommand};
+use notify_rust::Urgency;
+use serde::{Deserialize, Serialize};
 use std::process::Command;
-use std::sync::Arc;
-use std::time::Duration;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 use zbus::{blocking::Connection, zvariant::DynamicType, Message};
 
+use crate::daemon::Daemon;
+use crate::notifier::Notifier;
+use crate::utils::{format_duration, parse_duration};
+
+use self::inhibit::{InhibitOp, Inhibitors};
+
+/// How long a power action waits for confirmation when no delay is given
+const CONFIRM_DELAY: Duration = Duration::from_secs(30);
+
+/// Identifier of the scheduled power action, `None` if there is none
+static PENDING: Mutex<Option<u64>> = Mutex::new(None);
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
 #[derive(Clone)]
 pub struct System;
 
 impl System {
     pub fn poweroff() -> anyhow::Result<()> {
-        Self::call("poweroff", &true)?;
-        Ok(())
+        Self::power_action("PowerOff", "power off")
     }
 
     pub fn reboot() -> anyhow::Result<()> {
-        Self::call("Reboot", &true)?;
-        Ok(())
+        Self::power_action("Reboot", "reboot")
     }
 
     pub fn suspend() -> anyhow::Result<()> {
-        Self::call("Suspend", &true).and_then(|_| Self::lock())
+        Self::power_action("Suspend", "suspend").and_then(|_| Self::lock())
+    }
+
+    pub fn hibernate() -> anyhow::Result<()> {
+        Self::power_action("Hibernate", "hibernate").and_then(|_| Self::lock())
+    }
+
+    pub fn hybrid_sleep() -> anyhow::Result<()> {
+        Self::power_action("HybridSleep", "go to hybrid sleep").and_then(|_| Self::lock())
+    }
+
+    pub fn suspend_then_hibernate() -> anyhow::Result<()> {
+        Self::power_action("SuspendThenHibernate", "suspend then hibernate")
+            .and_then(|_| Self::lock())
+    }
+
+    /// Calls the logind `method` if the system is able to `describe`, notifying otherwise
+    fn power_action(method: &str, describe: &str) -> anyhow::Result<()> {
+        // logind answers `yes`, `no`, `challenge` (needs authentication) or `na`
+        let capability = Self::call(&format!("Can{method}"), &())?.body::<String>()?;
+        let reason = match capability.as_str() {
+            "yes" | "challenge" => {
+                Self::call(method, &true)?;
+                return Ok(());
+            }
+            "na" => "The system does not support it",
+            "no" => "It is not allowed",
+            _ => "It is not possible right now",
+        };
+        Notifier::new("system").send(
+            &format!("Cannot {describe}"),
+            reason,
+            Some(Urgency::Critical),
+            None,
+        )?;
+        Err(anyhow::anyhow!("Cannot {describe}: {reason}"))
     }
 
     pub fn lock() -> anyhow::Result<()> {
@@ -29,40 +79,163 @@ impl System {
         Ok(())
     }
 
-    pub fn inhibit(operation: InhibitOp) -> anyhow::Result<()> {
-        match operation {
-            InhibitOp::On => {
-                let _handle =
-                    Self::call("Inhibit", &("idle", "emanager", "Idle inhibitor", "block"))?;
-                loop {
-                    std::thread::sleep(Duration::from_secs(2_u64.pow(8)));
+    /// Runs `action` once `schedule` allows it, with a notification to cancel it meanwhile
+    fn schedule(
+        describe: &'static str,
+        action: fn() -> anyhow::Result<()>,
+        schedule: Schedule,
+    ) -> anyhow::Result<()> {
+        if schedule.delay.is_none() && !schedule.confirm {
+            return action();
+        }
+        // `system cancel` could not reach an action pending in a short-lived process
+        if !Daemon::runs_here()? {
+            return Err(anyhow::anyhow!(
+                "Delayed and confirmed actions are held by the daemon, which is not running"
+            ));
+        }
+        let delay = schedule.delay.unwrap_or(CONFIRM_DELAY);
+        let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+        // a new action replaces the pending one
+        *PENDING.lock().unwrap() = Some(id);
+
+        let notifier = Notifier::new("system");
+        let confirmed = Arc::new(AtomicBool::new(false));
+        let on_confirm = confirmed.clone();
+        let actions = if schedule.confirm {
+            vec![("confirm", "Confirm"), ("cancel", "Cancel")]
+        } else {
+            vec![("cancel", "Cancel")]
+        };
+        let body = move |left: Duration| {
+            if schedule.confirm {
+                format!("Confirm within {}", format_duration(left))
+            } else {
+                format!("In {}", format_duration(left))
+            }
+        };
+        notifier.send_with_actions(
+            describe,
+            &body(delay),
+            Some(Urgency::Critical),
+            &actions,
+            move |action| match action {
+                "confirm" => on_confirm.store(true, Ordering::Relaxed),
+                "cancel" => {
+                    Self::cancel_pending(id);
                 }
+                _ => (),
+            },
+        )?;
+
+        // the client gets its reply right away while the daemon counts down
+        std::thread::spawn(move || {
+            let started = Instant::now();
+            let mut refreshed = started;
+            while started.elapsed() < delay && !confirmed.load(Ordering::Relaxed) {
+                std::thread::sleep(Duration::from_millis(200));
+                if *PENDING.lock().unwrap() != Some(id) {
+                    return notifier.send(describe, "Cancelled", None, None);
+                }
+                if refreshed.elapsed() >= Duration::from_secs(1) {
+                    refreshed = Instant::now();
+                    let left = delay
+                        .saturating_sub(started.elapsed())
+                        .as_secs_f32()
+                        .round();
+                    let body = body(Duration::from_secs(left as u64));
+                    if let Err(e) = notifier.update_with_actions(
+                        describe,
+                        &body,
+                        Some(Urgency::Critical),
+                        &actions,
+                    ) {
+                        eprintln!("Could not update the countdown of '{describe}': {e}");
+                    }
+                }
+            }
+            // a cancellation that came in since the last check still counts
+            if !Self::cancel_pending(id) {
+                return notifier.send(describe, "Cancelled", None, None);
             }
-            InhibitOp::Off => {
-                Command::new("pkill")
-                    .args(["-f", "emanager system inhibit on"])
-                    .output()?;
-                Ok(())
+            if schedule.confirm && !confirmed.load(Ordering::Relaxed) {
+                return notifier.send(describe, "Not confirmed, cancelled", None, None);
             }
+            action().inspect_err(|e| eprintln!("{describe} failed: {e}"))
+        });
+        Ok(())
+    }
+
+    /// Aborts the scheduled power action
+    pub fn cancel() -> anyhow::Result<()> {
+        PENDING
+            .lock()
+            .unwrap()
+            .take()
+            .map(|_| ())
+            .ok_or(anyhow::anyhow!("No power action is pending"))
+    }
+
+    /// Clears the pending action if it is still `id`, returning whether it was
+    fn cancel_pending(id: u64) -> bool {
+        let mut pending = PENDING.lock().unwrap();
+        let still = *pending == Some(id);
+        if still {
+            *pending = None;
         }
+        still
     }
 
-    pub fn handle(operation: SystemOp) -> anyhow::Result<()> {
+    pub fn handle(operation: SystemOp) -> anyhow::Result<Option<String>> {
         match operation {
-            SystemOp::Poweroff => Self::poweroff(),
-            SystemOp::Reboot => Self::reboot(),
-            SystemOp::Suspend => Self::suspend(),
+            SystemOp::Poweroff { schedule } => {
+                Self::schedule("Powering off", Self::poweroff, schedule)
+            }
+            SystemOp::Reboot { schedule } => Self::schedule("Rebooting", Self::reboot, schedule),
+            SystemOp::Suspend { schedule } => Self::schedule("Suspending", Self::suspend, schedule),
+            SystemOp::Cancel => Self::cancel(),
+            SystemOp::Hibernate => Self::hibernate(),
+            SystemOp::HybridSleep => Self::hybrid_sleep(),
+            SystemOp::SuspendThenHibernate => Self::suspend_then_hibernate(),
             SystemOp::Lock => Self::lock(),
-            SystemOp::Inhibit { operation } => Self::inhibit(operation),
-        }
+            SystemOp::Inhibit { operation } => return Inhibitors::handle(operation),
+        }?;
+        Ok(None)
     }
 
     fn call(method: &str, body: &(impl Serialize + DynamicType)) -> anyhow::Result<Arc<Message>> {
+        Self::call_at(
+            "/org/freedesktop/login1",
+            "org.freedesktop.login1.Manager",
+            method,
+            body,
+        )
+    }
+
+    /// Calls `method` on the logind session emanager runs in
+    pub fn call_session(
+        method: &str,
+        body: &(impl Serialize + DynamicType),
+    ) -> anyhow::Result<Arc<Message>> {
+        Self::call_at(
+            "/org/freedesktop/login1/session/auto",
+            "org.freedesktop.login1.Session",
+            method,
+            body,
+        )
+    }
+
+    fn call_at(
+        path: &str,
+        interface: &str,
+        method: &str,
+        body: &(impl Serialize + DynamicType),
+    ) -> anyhow::Result<Arc<Message>> {
         let bus = Connection::system()?;
         let message = bus.call_method(
             Some("org.freedesktop.login1"),
-            "/org/freedesktop/login1",
-            Some("org.freedesktop.login1.Manager"),
+            path,
+            Some(interface),
             method,
             body,
         )?;
@@ -70,25 +243,47 @@ impl System {
     }
 }
 
-#[derive(Copy, Clone, Subcommand)]
+#[derive(Clone, Subcommand, Serialize, Deserialize)]
 pub enum SystemOp {
     /// Turn system off
-    Poweroff,
+    Poweroff {
+        #[command(flatten)]
+        schedule: Schedule,
+    },
     /// Reboot system
-    Reboot,
+    Reboot {
+        #[command(flatten)]
+        schedule: Schedule,
+    },
     /// Suspend system
-    Suspend,
+    Suspend {
+        #[command(flatten)]
+        schedule: Schedule,
+    },
+    /// Hibernate system
+    Hibernate,
+    /// Suspend and hibernate system at the same time
+    HybridSleep,
+    /// Suspend system, then hibernate it after a while
+    SuspendThenHibernate,
+    /// Cancel the pending power action
+    Cancel,
     /// Lock session
     Lock,
     /// Inhibit idle
     Inhibit {
-        #[arg(value_enum)]
+        #[command(subcommand)]
         operation: InhibitOp,
     },
 }
 
-#[derive(Copy, Clone, ValueEnum)]
-pub enum InhibitOp {
-    On,
-    Off,
+/// When a power action runs
+#[derive(Copy, Clone, Default, Args, Serialize, Deserialize)]
+pub struct Schedule {
+    /// Wait before acting, like `30s`, `5m` or `1h30m`
+    #[arg(long, value_parser = parse_duration)]
+    delay: Option<Duration>,
+    /// Only act once confirmed from the notification, giving up after the delay (30s by default)
+    #[arg(long)]
+    confirm: bool,
 }
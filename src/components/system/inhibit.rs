@@ -0,0 +1,198 @@
+use std::fmt::Display;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::anyhow;
+use clap::Subcommand;
+use serde::{Deserialize, Serialize};
+use zbus::Message;
+
+use crate::daemon::Daemon;
+use crate::logger::Logger;
+use crate::notifier::Notifier;
+use crate::utils::{format_duration, parse_duration};
+
+use super::System;
+
+const JSON_VIEW_NAME: &str = "inhibit-json";
+
+static INHIBITORS: Mutex<Inhibitors> = Mutex::new(Inhibitors {
+    holders: Vec::new(),
+    handle: None,
+});
+
+/// Someone keeping the computer awake
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Holder {
+    name: String,
+    why: Option<String>,
+    /// Unix time at which it expires
+    until: Option<u64>,
+}
+
+impl Holder {
+    fn remaining(&self) -> Option<Duration> {
+        self.until
+            .map(|until| Duration::from_secs(until.saturating_sub(now())))
+    }
+}
+
+impl Display for Holder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name)?;
+        if let Some(why) = &self.why {
+            write!(f, " ({why})")?;
+        }
+        if let Some(remaining) = self.remaining() {
+            write!(f, ", {} left", format_duration(remaining))?;
+        }
+        Ok(())
+    }
+}
+
+/// Idle inhibitors held by the daemon, along with the logind inhibitor taken while there is any
+pub struct Inhibitors {
+    holders: Vec<Holder>,
+    handle: Option<Arc<Message>>,
+}
+
+impl Inhibitors {
+    pub fn handle(operation: InhibitOp) -> anyhow::Result<Option<String>> {
+        if !Daemon::runs_here()? {
+            return Err(anyhow!(
+                "Inhibitors are held by the daemon, which is not running"
+            ));
+        }
+        // computed before locking, as panicking with the lock held would poison it for good
+        let until = match &operation {
+            InhibitOp::On {
+                duration: Some(duration),
+                ..
+            } => Some(
+                now()
+                    .checked_add(duration.as_secs())
+                    .ok_or(anyhow!("Inhibitor duration is too long"))?,
+            ),
+            _ => None,
+        };
+        let mut inhibitors = INHIBITORS.lock().unwrap();
+        match operation {
+            InhibitOp::On { name, why, .. } => {
+                inhibitors.holders.retain(|holder| holder.name != name);
+                inhibitors.holders.push(Holder { name, why, until });
+            }
+            InhibitOp::Off { name: Some(name) } => {
+                if !inhibitors.holders.iter().any(|holder| holder.name == name) {
+                    return Err(anyhow!("No inhibitor named '{name}'"));
+                }
+                inhibitors.holders.retain(|holder| holder.name != name);
+            }
+            InhibitOp::Off { name: None } => inhibitors.holders.clear(),
+            InhibitOp::Status => return Ok(Some(inhibitors.to_string())),
+        }
+        inhibitors.update()?;
+        Ok(None)
+    }
+
+    /// Whether idle is inhibited by emanager
+    pub fn inhibited() -> bool {
+        !INHIBITORS.lock().unwrap().holders.is_empty()
+    }
+
+    /// Releases the holders whose time is up, notifying about them
+    pub fn listen() -> anyhow::Result<()> {
+        loop {
+            std::thread::sleep(Duration::from_secs(1));
+            let mut inhibitors = INHIBITORS.lock().unwrap();
+            let (expired, holders) = inhibitors
+                .holders
+                .drain(..)
+                .partition::<Vec<Holder>, _>(|holder| holder.until.is_some_and(|u| u <= now()));
+            inhibitors.holders = holders;
+            if expired.is_empty() {
+                continue;
+            }
+            inhibitors.update()?;
+            for holder in expired {
+                let body = match holder.why {
+                    Some(why) => format!(
+                        "'{}' ({why}) no longer keeps the computer awake",
+                        holder.name
+                    ),
+                    None => format!("'{}' no longer keeps the computer awake", holder.name),
+                };
+                Notifier::new("inhibit").send("Idle inhibitor expired", &body, None, None)?;
+            }
+        }
+    }
+
+    pub fn init_view() -> anyhow::Result<()> {
+        INHIBITORS.lock().unwrap().update()
+    }
+
+    /// Takes or releases the logind inhibitor and publishes the holders
+    fn update(&mut self) -> anyhow::Result<()> {
+        if self.holders.is_empty() {
+            self.handle = None;
+        } else if self.handle.is_none() {
+            self.handle = Some(System::call(
+                "Inhibit",
+                &("idle", "emanager", "Idle inhibitor", "block"),
+            )?);
+        }
+        Logger::new(JSON_VIEW_NAME).send(&InhibitState {
+            inhibited: !self.holders.is_empty(),
+            holders: self.holders.clone(),
+        })
+    }
+}
+
+impl Display for Inhibitors {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.holders.is_empty() {
+            return write!(f, "Idle is not inhibited");
+        }
+        write!(f, "Idle inhibited by")?;
+        for holder in &self.holders {
+            write!(f, "\n  {holder}")?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Subcommand, Serialize, Deserialize)]
+pub enum InhibitOp {
+    /// Keep the computer awake
+    On {
+        /// Who inhibits, so that several holders can come and go independently
+        #[arg(long, default_value = "user")]
+        name: String,
+        /// Release automatically after a while, like `30m` or `1h`
+        #[arg(long = "for", value_parser = parse_duration)]
+        duration: Option<Duration>,
+        /// Why idle is inhibited
+        #[arg(long)]
+        why: Option<String>,
+    },
+    /// Release an inhibitor
+    Off {
+        /// Holder to release, all of them if not given
+        #[arg(long)]
+        name: Option<String>,
+    },
+    /// Print the inhibitors
+    Status,
+}
+
+#[derive(Serialize, Deserialize)]
+struct InhibitState {
+    inhibited: bool,
+    holders: Vec<Holder>,
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
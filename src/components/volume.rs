@@ -1,98 +1,242 @@
+use std::time::Duration;
+
+use crate::config::Config;
+use crate::logger::Logger;
 use crate::notifier::Notifier;
 use clap::Subcommand;
 use serde::{Deserialize, Serialize};
-use std::ffi::OsStr;
-use std::process::{Command, Output};
 
+use super::audio::{AudioBackend, AudioDevice, AudioEvent, AudioLevel, AudioStream, DeviceKind};
 use super::components::{NotifiableState, ScaledComponent};
+use super::fade::Fade;
 
-const PROGRAM: &str = "wpctl";
-const ID: &str = "@DEFAULT_AUDIO_SINK@";
 const JSON_VIEW_NAME: &str = "volume-json";
+const DEVICES_JSON_VIEW_NAME: &str = "volume-devices-json";
+const APPS_JSON_VIEW_NAME: &str = "volume-apps-json";
+const MAX_VOLUME: u32 = 100;
+const FADE_ID: &str = "volume";
 
-pub struct Volume;
+pub struct Volume {
+    backend: Box<dyn AudioBackend>,
+    fade: Duration,
+}
 
 impl Volume {
-    pub fn working() -> anyhow::Result<bool> {
-        Ok(Self::exec(&["get-volume", ID])?.stderr.is_empty())
+    pub fn new(config: &Config) -> anyhow::Result<Self> {
+        Ok(Self {
+            backend: config.audio_backend.backend()?,
+            fade: Duration::from_millis(config.fade.volume),
+        })
     }
 
-    pub fn muted() -> anyhow::Result<bool> {
-        let string = String::from_utf8(Self::exec(&["get-volume", ID])?.stdout)?;
-        Ok(string.contains("MUTED"))
+    /// Publishes the state of the default output and of the applications every time the audio
+    /// server reports a change
+    pub fn listen(config: &Config) -> anyhow::Result<()> {
+        let volume = Self::new(config)?;
+        // catches up with what happened while the audio server could not be reached
+        volume.init_view()?;
+        let mut current = volume.get_state()?;
+        let mut devices = volume.devices()?;
+        let mut apps = volume.apps()?;
+        config
+            .audio_backend
+            .backend()?
+            .subscribe(&mut |event| match event {
+                AudioEvent::Sink => {
+                    let state = volume.get_state()?;
+                    if state != current {
+                        // changes made by a fade are notified once it is over
+                        if !Fade::running(FADE_ID) {
+                            state.notify()?;
+                        }
+                        state.update_view()?;
+                        current = state;
+                    }
+                    let new_devices = volume.devices()?;
+                    if new_devices != devices {
+                        Logger::new(DEVICES_JSON_VIEW_NAME).send(&new_devices)?;
+                        devices = new_devices;
+                    }
+                    Ok(())
+                }
+                AudioEvent::Stream => {
+                    let new_apps = volume.apps()?;
+                    if new_apps != apps {
+                        Logger::new(APPS_JSON_VIEW_NAME).send(&new_apps)?;
+                        apps = new_apps;
+                    }
+                    Ok(())
+                }
+                AudioEvent::Source => Ok(()),
+            })
     }
 
-    pub fn mute() -> anyhow::Result<()> {
-        Self::exec(&["set-mute", ID, "toggle"])?;
-        Self::update(0)
+    pub fn mute(&self) -> anyhow::Result<()> {
+        if let Some(level) = self.backend.get(DeviceKind::Sink)? {
+            self.backend.set_mute(DeviceKind::Sink, !level.muted)?;
+        }
+        self.update(0)
     }
 
-    pub fn handle(operation: VolumeOp) -> anyhow::Result<()> {
+    pub fn handle(&self, operation: VolumeOp) -> anyhow::Result<Option<String>> {
         match operation {
-            VolumeOp::Up { percent } => Self::up(percent),
-            VolumeOp::Down { percent } => Self::down(percent),
-            VolumeOp::Set { percent } => Self::set(percent),
-            VolumeOp::Mute => Self::mute(),
-            VolumeOp::Update => Self::update(500),
-        }
+            VolumeOp::Up { percent } => self.up(percent),
+            VolumeOp::Down { percent } => self.down(percent),
+            VolumeOp::Set { percent } => self.set(percent),
+            VolumeOp::Mute => self.mute(),
+            VolumeOp::Update => self.update(500),
+            VolumeOp::Devices => return Ok(Some(AudioDevice::list(&self.devices()?))),
+            VolumeOp::Select { device } => self.select(&device),
+            VolumeOp::App { operation } => return self.handle_app(operation),
+        }?;
+        Ok(None)
     }
 
-    fn exec(args: &[impl AsRef<OsStr>]) -> anyhow::Result<Output> {
-        let output = Command::new(PROGRAM).args(args).output()?;
-        Ok(output)
+    fn devices(&self) -> anyhow::Result<Vec<AudioDevice>> {
+        self.backend.devices(DeviceKind::Sink)
     }
 
-    pub(crate) fn init_view() -> anyhow::Result<()> {
-        let (working, muted, value) = (Self::working()?, Self::muted()?, Self::get()?);
-        let state = VolumeState::new(value, muted, working);
-        state.update_view()
+    /// Makes the output matching `query` the default one
+    fn select(&self, query: &str) -> anyhow::Result<()> {
+        let device = AudioDevice::find(self.devices()?, query)?;
+        self.backend.set_default(DeviceKind::Sink, &device)?;
+        Notifier::new("volume").send(
+            "Volume",
+            &format!("Output set to {}", device.description),
+            None,
+            None,
+        )?;
+        self.get_state()?.update_view()?;
+        Logger::new(DEVICES_JSON_VIEW_NAME).send(&self.devices()?)
     }
-}
 
-impl ScaledComponent<VolumeState> for Volume {
-    fn get() -> anyhow::Result<u32> {
-        if Self::working()? {
-            let string = String::from_utf8(Self::exec(&["get-volume", ID])?.stdout)?;
-            let volume = string.split(' ').collect::<Vec<&str>>()[1]
-                .trim()
-                .parse::<f32>()?;
-            Ok((volume * 100.).round() as u32)
-        } else {
-            Ok(0)
+    fn handle_app(&self, operation: AppOp) -> anyhow::Result<Option<String>> {
+        match operation {
+            AppOp::List => {
+                let streams = self.backend.streams()?;
+                return Ok(Some(if streams.is_empty() {
+                    "No application is playing sound".to_string()
+                } else {
+                    streams
+                        .iter()
+                        .map(AudioStream::to_string)
+                        .collect::<Vec<String>>()
+                        .join("\n")
+                }));
+            }
+            AppOp::Set { app, percent } => self.change_app(&app, |_| percent),
+            AppOp::Up { app, percent } => self.change_app(&app, |volume| volume + percent),
+            AppOp::Down { app, percent } => {
+                self.change_app(&app, |volume| volume.saturating_sub(percent))
+            }
+            AppOp::Mute { app } => self.mute_app(&app),
+        }?;
+        Ok(None)
+    }
+
+    /// States of the applications playing sound
+    fn apps(&self) -> anyhow::Result<Vec<AppState>> {
+        Ok(self
+            .backend
+            .streams()?
+            .into_iter()
+            .map(|stream| AppState {
+                id: stream.id,
+                state: VolumeState::new(stream.level.volume, stream.level.muted, true),
+                app: stream.app,
+            })
+            .collect())
+    }
+
+    /// Sets the volume of the streams of `app` to what `f` returns from their current one,
+    /// unless they are muted
+    fn change_app(&self, app: &str, f: impl Fn(u32) -> u32) -> anyhow::Result<()> {
+        for stream in AudioStream::find(self.backend.streams()?, app)? {
+            if !stream.level.muted {
+                self.backend
+                    .set_stream_volume(&stream, f(stream.level.volume).min(MAX_VOLUME))?;
+            }
         }
+        self.update_app(app)
     }
 
-    fn set(percent: u32) -> anyhow::Result<()> {
-        if !Self::muted()? {
-            Self::exec(&["set-volume", ID, &format!("{percent}%"), "-l", "1"])?;
+    /// Toggles mute for all the streams of `app`
+    fn mute_app(&self, app: &str) -> anyhow::Result<()> {
+        let streams = AudioStream::find(self.backend.streams()?, app)?;
+        let muted = !streams.iter().all(|stream| stream.level.muted);
+        for stream in streams {
+            self.backend.set_stream_mute(&stream, muted)?;
         }
-        Self::update(0)
+        self.update_app(app)
     }
 
-    fn up(percent: u32) -> anyhow::Result<()> {
-        if !Self::muted()? {
-            Self::exec(&["set-volume", ID, &format!("{percent}%+"), "-l", "1"])?;
+    /// Notifies the new state of `app` and publishes the states of all applications
+    fn update_app(&self, app: &str) -> anyhow::Result<()> {
+        if let Some(stream) = AudioStream::find(self.backend.streams()?, app)?.first() {
+            VolumeState::new(stream.level.volume, stream.level.muted, true)
+                .notify_as(&stream.app)?;
         }
-        Self::update(0)
+        Logger::new(APPS_JSON_VIEW_NAME).send(&self.apps()?)
     }
 
-    fn down(percent: u32) -> anyhow::Result<()> {
-        if !Self::muted()? {
-            Self::exec(&["set-volume", ID, &format!("{percent}%-")])?;
+    /// Sets the volume to what `f` returns from the current one, unless the output is muted,
+    /// fading if configured
+    fn change(&self, f: impl FnOnce(u32) -> u32) -> anyhow::Result<()> {
+        if let Some(AudioLevel {
+            volume,
+            muted: false,
+        }) = self.backend.get(DeviceKind::Sink)?
+        {
+            let done = Fade::run(
+                FADE_ID,
+                self.fade,
+                volume,
+                |volume| f(volume).min(MAX_VOLUME),
+                |volume| self.backend.set_volume(DeviceKind::Sink, volume),
+            )?;
+            if !done {
+                return Ok(());
+            }
         }
-        Self::update(0)
+        self.update(0)
+    }
+}
+
+impl ScaledComponent<VolumeState> for Volume {
+    fn get(&self) -> anyhow::Result<u32> {
+        Ok(self
+            .backend
+            .get(DeviceKind::Sink)?
+            .map_or(0, |level| level.volume))
+    }
+
+    fn set(&self, percent: u32) -> anyhow::Result<()> {
+        self.change(|_| percent)
+    }
+
+    fn up(&self, percent: u32) -> anyhow::Result<()> {
+        self.change(|volume| volume + percent)
+    }
+
+    fn down(&self, percent: u32) -> anyhow::Result<()> {
+        self.change(|volume| volume.saturating_sub(percent))
+    }
+
+    fn init_view(&self) -> anyhow::Result<()> {
+        self.get_state()?.update_view()?;
+        Logger::new(DEVICES_JSON_VIEW_NAME).send(&self.devices()?)?;
+        Logger::new(APPS_JSON_VIEW_NAME).send(&self.apps()?)
     }
 
-    fn get_state() -> anyhow::Result<VolumeState> {
-        Ok(VolumeState::new(
-            Self::get()?,
-            Self::muted()?,
-            Self::working()?,
-        ))
+    fn get_state(&self) -> anyhow::Result<VolumeState> {
+        Ok(match self.backend.get(DeviceKind::Sink)? {
+            Some(level) => VolumeState::new(level.volume, level.muted, true),
+            None => VolumeState::new(0, false, false),
+        })
     }
 }
 
-#[derive(Copy, Clone, Subcommand)]
+#[derive(Clone, Subcommand, Serialize, Deserialize)]
 pub enum VolumeOp {
     /// Increase by percentage
     Up {
@@ -113,10 +257,54 @@ pub enum VolumeOp {
     Mute,
     /// Update status and notify
     Update,
+    /// List output devices
+    Devices,
+    /// Set the default output device
+    Select {
+        /// Id, name or description of the device
+        device: String,
+    },
+    /// Control the volume of an application
+    App {
+        #[command(subcommand)]
+        operation: AppOp,
+    },
 }
 
-#[derive(Serialize, Deserialize)]
-struct VolumeState {
+#[derive(Clone, Subcommand, Serialize, Deserialize)]
+pub enum AppOp {
+    /// List applications playing sound
+    List,
+    /// Set to a percentage
+    Set {
+        /// Id or name of the application
+        app: String,
+        #[arg(value_parser = clap::value_parser!(u32).range(0..=100))]
+        percent: u32,
+    },
+    /// Increase by percentage
+    Up {
+        /// Id or name of the application
+        app: String,
+        #[arg(default_value_t = 5, value_parser = clap::value_parser!(u32).range(0..=100))]
+        percent: u32,
+    },
+    /// Decrease by percentage
+    Down {
+        /// Id or name of the application
+        app: String,
+        #[arg(default_value_t = 5, value_parser = clap::value_parser!(u32).range(0..=100))]
+        percent: u32,
+    },
+    /// Toggle mute
+    Mute {
+        /// Id or name of the application
+        app: String,
+    },
+}
+
+#[derive(Serialize, Deserialize, PartialEq)]
+pub struct VolumeState {
     value: u32,
     muted: bool,
     working: bool,
@@ -142,26 +330,40 @@ impl VolumeState {
             icon,
         }
     }
-}
 
-impl NotifiableState for VolumeState {
-    fn notify(&self) -> anyhow::Result<()> {
+    /// Notifies the state with `summary` as title
+    fn notify_as(&self, summary: &str) -> anyhow::Result<()> {
         let notifier = Notifier::new("volume");
         if !self.working {
-            notifier.send("Volume", "No output", None, None)
+            notifier.send(summary, "No output", None, None)
         } else if self.muted {
-            notifier.send("Volume", "Muted", None, None)
+            notifier.send(summary, "Muted", None, None)
         } else {
             notifier.send(
-                "Volume",
+                summary,
                 &format!("Set to {}%", self.value),
                 None,
                 Some(self.value),
             )
         }
     }
+}
+
+impl NotifiableState for VolumeState {
+    fn notify(&self) -> anyhow::Result<()> {
+        self.notify_as("Volume")
+    }
 
     fn json_name(&self) -> &str {
         JSON_VIEW_NAME
     }
 }
+
+/// Volume of an application, as published in `volume-apps-json`
+#[derive(Serialize, Deserialize, PartialEq)]
+struct AppState {
+    id: u32,
+    app: String,
+    #[serde(flatten)]
+    state: VolumeState,
+}
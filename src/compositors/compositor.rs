@@ -15,6 +15,8 @@ pub trait Compositor {
     fn set_layout(layout_to_set: &Layout, config_layouts: &[Layout]) -> anyhow::Result<()>;
     fn get_first_layout_sequence() -> anyhow::Result<Layout>;
     fn get_corresponding_compositor_type() -> CompositorType;
+    /// Turns all monitors on or off
+    fn set_screen(on: bool) -> anyhow::Result<()>;
 
     fn get_color() -> String {
         // temporary fix because hyprctl doesn't work for colors
@@ -3,6 +3,7 @@ use crate::config::CompositorType;
 use crate::logger::Logger;
 use hyprland::{
     data::{Client, Workspace, Workspaces},
+    dispatch::{Dispatch, DispatchType},
     keyword::Keyword,
     shared::{HyprData, HyprDataActive, HyprDataActiveOptional},
 };
@@ -57,6 +58,11 @@ impl Compositor for Hypr {
         CompositorType::Hyprland
     }
 
+    fn set_screen(on: bool) -> anyhow::Result<()> {
+        Dispatch::call(DispatchType::ToggleDPMS(on, None))?;
+        Ok(())
+    }
+
     fn set_layout(layout_to_set: &Layout, config_layouts: &[Layout]) -> anyhow::Result<()> {
         let (layouts, layouts_var) = config_layouts.iter().fold(
             (
@@ -40,6 +40,24 @@ impl Compositor for Niri {
         CompositorType::Niri
     }
 
+    fn set_screen(on: bool) -> anyhow::Result<()> {
+        let action = if on {
+            "power-on-monitors"
+        } else {
+            "power-off-monitors"
+        };
+        if Command::new(NIRI_CMD)
+            .args(["msg", "action", action])
+            .output()?
+            .stderr
+            .is_empty()
+        {
+            Ok(())
+        } else {
+            Err(anyhow::anyhow!("Error: Could not run '{action}'"))
+        }
+    }
+
     fn set_layout(layout_to_set: &Layout, _: &[Layout]) -> anyhow::Result<()> {
         let available_layouts = Self::get_available_layouts()?;
         let current_layout = Layout::try_from_sequence()?;
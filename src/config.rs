@@ -1,9 +1,18 @@
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    components::layout::{Layout, LayoutOp},
+    components::{
+        ac::AcConfig,
+        audio::AudioBackendType,
+        battery::BatteryConfig,
+        brightness::BrightnessCurve,
+        fade::FadeConfig,
+        idle::IdleConfig,
+        layout::{Layout, LayoutOp},
+        power_profile::PowerProfileConfig,
+    },
     compositors::{compositor::Compositor, hypr::Hypr, niri::Niri},
     logger::Logger,
 };
@@ -34,6 +43,13 @@ impl CompositorType {
         }
     }
 
+    pub(crate) fn set_screen(&self, on: bool) -> anyhow::Result<()> {
+        match self {
+            CompositorType::Hyprland => Hypr::set_screen(on),
+            CompositorType::Niri => Niri::set_screen(on),
+        }
+    }
+
     pub(crate) fn get_running_compositor_type() -> anyhow::Result<Self> {
         if crate::compositors::hypr::Hypr::running() {
             Ok(Self::Hyprland)
@@ -71,12 +87,25 @@ impl Default for CompositorType {
 pub struct Config {
     pub layouts: Vec<Layout>,
     pub compositor_type: CompositorType,
+    pub audio_backend: AudioBackendType,
+    /// Where sysfs is mounted, may point to a fake tree for testing
+    pub sysfs_root: PathBuf,
+    /// Also drive external monitors over DDC/CI, requires `ddcutil`
+    pub ddc: bool,
+    pub brightness: BrightnessCurve,
+    pub fade: FadeConfig,
+    pub battery: BatteryConfig,
+    pub power_profile: PowerProfileConfig,
+    /// Settings applied when plugging in or out
+    pub ac: AcConfig,
+    pub idle: IdleConfig,
 }
 
 impl Config {
     pub fn from_file<P: AsRef<Path>>(path: P) -> anyhow::Result<Self> {
         if path.as_ref().exists() {
             let config: Config = toml::from_str(&std::fs::read_to_string(path)?)?;
+            config.brightness.validate()?;
             if !Logger::<bool>::new(LAYOUT_SEQUENCE_FILENAME).try_exists()? {
                 config.init_layout_sequence()?;
             }
@@ -245,6 +274,15 @@ impl Default for Config {
         Self {
             compositor_type: CompositorType::default(),
             layouts: vec![Layout::new("fr", None), Layout::new("us", None)],
+            audio_backend: AudioBackendType::default(),
+            sysfs_root: PathBuf::from("/sys"),
+            ddc: false,
+            brightness: BrightnessCurve::default(),
+            fade: FadeConfig::default(),
+            battery: BatteryConfig::default(),
+            power_profile: PowerProfileConfig::default(),
+            ac: AcConfig::default(),
+            idle: IdleConfig::default(),
         }
     }
 }
@@ -0,0 +1,182 @@
+use crate::lock::Lock;
+use crate::utils::format_duration;
+use anyhow::anyhow;
+use clap::Subcommand;
+use serde::{Deserialize, Serialize};
+use std::any::Any;
+use std::fmt::Display;
+use std::panic::AssertUnwindSafe;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+pub const LOCK_NAME: &str = "daemon";
+const MIN_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(64);
+
+/// Runtime state of the daemon, shared between its threads
+pub struct Daemon {
+    started: Instant,
+    listeners: Mutex<Vec<ListenerStatus>>,
+}
+
+impl Daemon {
+    pub fn new() -> Self {
+        Self {
+            started: Instant::now(),
+            listeners: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Runs `listener` until it returns successfully, restarting it with an exponential backoff
+    /// every time it fails
+    pub fn supervise(&self, name: &str, listener: impl Fn() -> anyhow::Result<()>) {
+        let mut backoff = MIN_BACKOFF;
+        loop {
+            self.update(name, |status| status.state = ListenerState::Running);
+            let started = Instant::now();
+            // a panicking listener is a failure too, rather than a silently dead thread
+            let result = std::panic::catch_unwind(AssertUnwindSafe(&listener))
+                .unwrap_or_else(|panic| Err(anyhow!("Panicked: {}", Self::panic_message(&panic))));
+            if started.elapsed() > MAX_BACKOFF {
+                backoff = MIN_BACKOFF;
+            }
+            match result {
+                Ok(()) => {
+                    self.update(name, |status| status.state = ListenerState::Stopped);
+                    return;
+                }
+                Err(e) => {
+                    eprintln!("Listener '{name}' failed, restarting in {backoff:?}: {e}");
+                    self.update(name, |status| {
+                        status.state = ListenerState::Restarting;
+                        status.restarts += 1;
+                        status.last_error = Some(e.to_string());
+                    });
+                    std::thread::sleep(backoff);
+                    backoff = (backoff * 2).min(MAX_BACKOFF);
+                }
+            }
+        }
+    }
+
+    fn panic_message(panic: &Box<dyn Any + Send>) -> String {
+        panic
+            .downcast_ref::<&str>()
+            .map(|message| message.to_string())
+            .or_else(|| panic.downcast_ref::<String>().cloned())
+            .unwrap_or("unknown reason".to_string())
+    }
+
+    /// Whether this process is the running daemon, which holds state between commands
+    pub fn runs_here() -> anyhow::Result<bool> {
+        Ok(Lock::owner(LOCK_NAME)? == Some(std::process::id()))
+    }
+
+    pub fn status(&self) -> DaemonStatus {
+        let listeners = self.listeners.lock().unwrap();
+        DaemonStatus {
+            pid: std::process::id(),
+            uptime: self.started.elapsed().as_secs(),
+            listeners: listeners.clone(),
+        }
+    }
+
+    fn update(&self, name: &str, f: impl FnOnce(&mut ListenerStatus)) {
+        let mut listeners = self.listeners.lock().unwrap();
+        match listeners.iter_mut().find(|l| l.name == name) {
+            Some(listener) => f(listener),
+            None => {
+                let mut listener = ListenerStatus::new(name);
+                f(&mut listener);
+                listeners.push(listener);
+            }
+        }
+    }
+}
+
+impl Default for Daemon {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Copy, Clone, Subcommand, Serialize, Deserialize)]
+pub enum DaemonOp {
+    /// Show PID, uptime and listeners of the running daemon
+    Status,
+    /// Stop the running daemon
+    Stop,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct DaemonStatus {
+    pid: u32,
+    uptime: u64,
+    listeners: Vec<ListenerStatus>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+struct ListenerStatus {
+    name: String,
+    state: ListenerState,
+    restarts: u32,
+    last_error: Option<String>,
+}
+
+impl ListenerStatus {
+    fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            state: ListenerState::Running,
+            restarts: 0,
+            last_error: None,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq)]
+enum ListenerState {
+    Running,
+    Restarting,
+    Stopped,
+}
+
+impl Display for ListenerState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ListenerState::Running => write!(f, "running"),
+            ListenerState::Restarting => write!(f, "restarting"),
+            ListenerState::Stopped => write!(f, "stopped"),
+        }
+    }
+}
+
+impl Display for DaemonStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "PID: {}", self.pid)?;
+        writeln!(
+            f,
+            "Uptime: {}",
+            format_duration(Duration::from_secs(self.uptime))
+        )?;
+        write!(
+            f,
+            "Listeners: {}/{} running",
+            self.listeners
+                .iter()
+                .filter(|l| l.state == ListenerState::Running)
+                .count(),
+            self.listeners.len()
+        )?;
+        for listener in &self.listeners {
+            write!(f, "\n  {}: {}", listener.name, listener.state)?;
+            if listener.restarts > 0 {
+                write!(f, " ({} restarts)", listener.restarts)?;
+            }
+            if let Some(e) = listener.last_error.as_ref() {
+                write!(f, "\n    last error: {e}")?;
+            }
+        }
+        Ok(())
+    }
+}
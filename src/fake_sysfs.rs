@@ -0,0 +1,45 @@
+use std::fmt::Display;
+use std::path::{Path, PathBuf};
+
+/// Sysfs tree written under the temporary directory for tests, removed when dropped
+pub struct FakeSysfs(PathBuf);
+
+impl FakeSysfs {
+    /// Starts an empty tree, `name` keeping apart the trees of tests running at the same time
+    pub fn new(name: &str) -> Self {
+        let root = std::env::temp_dir().join(format!("emanager-{name}-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(&root).unwrap();
+        Self(root)
+    }
+
+    /// Adds the directory at `path`, relative to the root
+    pub fn dir(self, path: &str) -> Self {
+        std::fs::create_dir_all(self.path(path)).unwrap();
+        self
+    }
+
+    /// Adds the attribute at `path` holding `value`, like the kernel with a trailing newline
+    pub fn file(self, path: &str, value: impl Display) -> Self {
+        let file = self.path(path);
+        if let Some(dir) = file.parent() {
+            std::fs::create_dir_all(dir).unwrap();
+        }
+        std::fs::write(file, format!("{value}\n")).unwrap();
+        self
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.0
+    }
+
+    pub fn path(&self, path: &str) -> PathBuf {
+        self.0.join(path)
+    }
+}
+
+impl Drop for FakeSysfs {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
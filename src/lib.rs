@@ -5,11 +5,16 @@ pub mod compositors {
 }
 
 pub mod components {
+    pub mod ac;
+    pub mod audio;
     pub mod battery;
     pub mod brightness;
     pub mod components;
+    pub mod fade;
+    pub mod idle;
     pub mod layout;
     pub mod microphone;
+    pub mod power_profile;
     pub mod system;
     pub mod volume;
     pub mod wifi;
@@ -18,7 +23,12 @@ pub mod components {
 pub mod acpi;
 pub mod args;
 pub mod config;
+pub mod daemon;
+#[cfg(test)]
+mod fake_sysfs;
+pub mod lock;
 pub mod logger;
 pub mod manager;
 pub mod notifier;
+pub mod socket;
 pub mod utils;
@@ -0,0 +1,53 @@
+use crate::utils::runtime_dir;
+use std::fs::{File, OpenOptions, TryLockError};
+use std::io::{Read, Write};
+use std::path::PathBuf;
+
+/// An exclusive lock on `$XDG_RUNTIME_DIR/emanager/<name>.lock` holding the PID of its owner.
+/// The lock is released when dropped or when the owning process dies.
+pub struct Lock {
+    _file: File,
+}
+
+impl Lock {
+    /// Takes the lock, returns `None` if another process already holds it
+    pub fn acquire(name: &str) -> anyhow::Result<Option<Self>> {
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(Self::path(name)?)?;
+        match file.try_lock() {
+            Ok(()) => (),
+            Err(TryLockError::WouldBlock) => return Ok(None),
+            Err(TryLockError::Error(e)) => return Err(e.into()),
+        }
+        file.set_len(0)?;
+        file.write_all(std::process::id().to_string().as_bytes())?;
+        file.flush()?;
+        Ok(Some(Self { _file: file }))
+    }
+
+    /// Returns the PID of the process holding the lock, if any
+    pub fn owner(name: &str) -> anyhow::Result<Option<u32>> {
+        let mut file = match File::open(Self::path(name)?) {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        match file.try_lock_shared() {
+            Ok(()) => Ok(None),
+            Err(TryLockError::WouldBlock) => {
+                let mut pid = String::new();
+                file.read_to_string(&mut pid)?;
+                Ok(pid.trim().parse().ok())
+            }
+            Err(TryLockError::Error(e)) => Err(e.into()),
+        }
+    }
+
+    fn path(name: &str) -> anyhow::Result<PathBuf> {
+        Ok(runtime_dir()?.join(format!("{name}.lock")))
+    }
+}
@@ -54,6 +54,41 @@ impl<T: Serialize + for<'a> Deserialize<'a>> Logger<T> {
         Ok(())
     }
 
+    /// Appends `state`, moving the file aside once it is bigger than `max` bytes so that the
+    /// previous states are kept
+    pub fn append(&self, state: &T, max: u64) -> anyhow::Result<()> {
+        std::fs::create_dir_all(&self.full_path)?;
+        if std::fs::metadata(&self.file).is_ok_and(|metadata| metadata.len() > max) {
+            std::fs::rename(&self.file, self.old_file())?;
+        }
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.file)?;
+        let json = serde_json::to_vec(&state)?;
+        file.write_all(&json)?;
+        file.write_all(b"\n")?;
+        Ok(())
+    }
+
+    /// Returns the states written by `append`, oldest first
+    pub fn read_all(&self) -> anyhow::Result<Vec<T>> {
+        let mut states = Vec::new();
+        for file in [self.old_file(), self.file.clone()] {
+            let content = match std::fs::read_to_string(&file) {
+                Ok(content) => content,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+                Err(e) => return Err(e.into()),
+            };
+            states.extend(content.lines().flat_map(|line| serde_json::from_str(line)));
+        }
+        Ok(states)
+    }
+
+    fn old_file(&self) -> String {
+        format!("{}.old", self.file)
+    }
+
     pub fn read(&self) -> anyhow::Result<T> {
         let state = std::fs::read_to_string(&self.file)?
             .lines()
@@ -78,9 +113,11 @@ impl<T: Serialize + for<'a> Deserialize<'a>> Logger<T> {
 
     pub fn overwrite(&self, state: &T) -> anyhow::Result<()> {
         std::fs::create_dir_all(&self.full_path)?;
+        // a shorter state would otherwise leave the end of the previous one behind
         let mut file = std::fs::OpenOptions::new()
             .write(true)
             .create(true)
+            .truncate(true)
             .open(&self.file)?;
         let json = serde_json::to_vec(&state)?;
         file.write_all(&json)?;
@@ -1,8 +1,28 @@
 use clap::Parser;
-use emanager::{args, config, manager};
+use emanager::{args, config, manager, socket};
 
 fn main() {
     let args = args::Args::parse();
+
+    if args.command.routable() {
+        match socket::Socket::send(&args.command) {
+            Ok(Some(reply)) => {
+                match reply {
+                    socket::Reply::Ok => (),
+                    socket::Reply::Output(output) => println!("{output}"),
+                    socket::Reply::Err(e) => eprintln!("{e}"),
+                }
+                return;
+            }
+            Ok(None) => (),
+            // the daemon may have received the command, handling it again could apply it twice
+            Err(e) => {
+                eprintln!("Could not get a reply from the daemon: {e}");
+                return;
+            }
+        }
+    }
+
     let config = dirs::home_dir().map_or(Ok(config::Config::default()), |home| {
         config::Config::from_file(home.join(".config/emanager/config.toml"))
     });
@@ -13,11 +33,16 @@ fn main() {
     let config = config.unwrap();
 
     let result = match args.command {
-        args::Command::Daemon => manager::Manager::daemon(&config),
+        args::Command::Daemon {
+            replace,
+            operation: None,
+        } => manager::Manager::daemon(&config, replace).map(|_| None),
         _ => manager::Manager::handle(args.command, &config),
     };
 
-    if let Err(e) = result {
-        eprintln!("{e}");
+    match result {
+        Ok(Some(output)) => println!("{output}"),
+        Ok(None) => (),
+        Err(e) => eprintln!("{e}"),
     }
 }
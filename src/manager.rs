@@ -2,68 +2,142 @@ use crate::acpi::Acpi;
 use crate::args::Command;
 use crate::components::components::ScaledComponent;
 use crate::components::{
-    battery::Battery, brightness::Brightness, microphone::Microphone, system::System,
-    volume::Volume, wifi::Wifi,
+    ac::Ac,
+    battery::Battery,
+    brightness::Brightness,
+    idle::Idle,
+    microphone::Microphone,
+    power_profile::PowerProfile,
+    system::{inhibit::Inhibitors, System},
+    volume::Volume,
+    wifi::Wifi,
 };
 use crate::compositors::{compositor::Compositor, hypr::Hypr, niri::Niri};
 use crate::config::Config;
+use crate::daemon::{Daemon, DaemonOp, LOCK_NAME};
+use crate::lock::Lock;
+use crate::socket::Socket;
 use anyhow::anyhow;
+use std::time::Duration;
 
 pub struct Manager;
 
 impl Manager {
-    pub fn daemon(config: &Config) -> anyhow::Result<()> {
-        if Self::running() {
-            return Err(anyhow!("Manager is already running"));
+    pub fn daemon(config: &Config, replace: bool) -> anyhow::Result<()> {
+        if replace {
+            Self::stop()?;
         }
+        let _lock = Lock::acquire(LOCK_NAME)?.ok_or(anyhow!("Manager is already running"))?;
+        let daemon = Daemon::new();
+
         let seq = config.get_layout_sequence()?;
         let current_layout = config.compositor_type.get_first_layout_sequence()?;
         config.set_layout(
             &seq.first().expect("Error: sequence is empty"), // should never occure
             Some(&current_layout),
         )?;
+        if let Err(e) = Battery::apply_limit(config) {
+            eprintln!("Could not apply the charge limit: {e}");
+        }
+        if let Err(e) = Ac::init(config) {
+            eprintln!("Could not read the power adapters: {e}");
+        }
         Self::init_view(config)?;
-        std::thread::scope(|scope| -> anyhow::Result<()> {
-            let handle = scope.spawn(|| Acpi::listen(config));
-            scope.spawn(|| Battery::listen());
+        std::thread::scope(|scope| {
+            scope.spawn(|| daemon.supervise("acpi", || Acpi::listen(config)));
+            scope.spawn(|| daemon.supervise("battery", || Battery::listen(config)));
             match config.compositor_type {
-                crate::config::CompositorType::Hyprland => scope.spawn(|| Hypr::listen()),
-                crate::config::CompositorType::Niri => scope.spawn(|| Niri::listen()),
+                crate::config::CompositorType::Hyprland => {
+                    scope.spawn(|| daemon.supervise("compositor", Hypr::listen))
+                }
+                crate::config::CompositorType::Niri => {
+                    scope.spawn(|| daemon.supervise("compositor", Niri::listen))
+                }
             };
-            scope.spawn(|| Wifi::listen());
-
-            handle.join().unwrap()
-        })
+            scope.spawn(|| daemon.supervise("wifi", Wifi::listen));
+            scope.spawn(|| daemon.supervise("volume", || Volume::listen(config)));
+            scope.spawn(|| daemon.supervise("microphone", || Microphone::listen(config)));
+            scope.spawn(|| daemon.supervise("power-profile", PowerProfile::listen));
+            scope.spawn(|| daemon.supervise("idle", || Idle::listen(config)));
+            scope.spawn(|| daemon.supervise("inhibit", Inhibitors::listen));
+            scope.spawn(|| daemon.supervise("socket", || Socket::listen(config, &daemon)));
+        });
+        Ok(())
     }
 
     fn init_view(config: &Config) -> anyhow::Result<()> {
         config.init_view()?;
-        Volume::init_view()?;
-        Brightness::init_view()?;
+        Brightness::new(config, None)?.init_view()?;
         Wifi::init_view()?;
-        Battery::init_view()?;
-        Microphone::init_view()?;
+        Battery::init_view(config)?;
+        Inhibitors::init_view()?;
+        // the audio server may not be up yet, the listeners publish its state once it is
+        if let Err(e) = Volume::new(config).and_then(|v| v.init_view()) {
+            eprintln!("Could not get the volume: {e}");
+        }
+        if let Err(e) = Microphone::new(config).and_then(|m| m.init_view()) {
+            eprintln!("Could not get the microphone: {e}");
+        }
+        // power-profiles-daemon is optional
+        if let Err(e) = PowerProfile::new().and_then(|p| p.init_view()) {
+            eprintln!("Could not get the power profile: {e}");
+        }
         Ok(())
     }
 
-    pub fn handle(command: Command, config: &Config) -> anyhow::Result<()> {
+    /// Handles `command`, returning what should be printed to the user if any
+    pub fn handle(command: Command, config: &Config) -> anyhow::Result<Option<String>> {
         match command {
-            Command::System { operation } => System::handle(operation),
-            Command::Brightness { operation } => Brightness::handle(operation),
-            Command::Volume { operation } => Volume::handle(operation),
+            Command::Volume { operation } => return Volume::new(config)?.handle(operation),
+            Command::Microphone { operation } => return Microphone::new(config)?.handle(operation),
+            Command::System { operation } => return System::handle(operation),
+            Command::Battery { operation } => return Battery::handle(operation, config),
+            Command::PowerProfile { operation } => return PowerProfile::new()?.handle(operation),
+            Command::Brightness { device, operation } => {
+                return Brightness::new(config, device.as_deref())?.handle(operation)
+            }
             Command::Layout { operation } => config.change_layout(operation),
             Command::Wifi { operation } => Wifi::handle(operation),
-            Command::Microphone { operation } => Microphone::handle(operation),
-            Command::Daemon => Ok(()),
+            Command::Daemon {
+                operation: Some(_), ..
+            } => Err(anyhow!("Manager is not running")),
+            Command::Daemon {
+                operation: None, ..
+            } => Ok(()),
+        }?;
+        Ok(None)
+    }
+
+    /// Asks the running daemon to stop and waits for it to release its lock,
+    /// killing it if it does not answer in time
+    fn stop() -> anyhow::Result<()> {
+        let Some(pid) = Lock::owner(LOCK_NAME)? else {
+            return Ok(());
+        };
+        let _ = Socket::send(&Command::Daemon {
+            replace: false,
+            operation: Some(DaemonOp::Stop),
+        });
+        if Self::wait_stopped()? {
+            return Ok(());
+        }
+        std::process::Command::new("kill")
+            .arg(pid.to_string())
+            .output()?;
+        if Self::wait_stopped()? {
+            Ok(())
+        } else {
+            Err(anyhow!("Could not stop the running manager (PID {pid})"))
         }
     }
 
-    pub fn running() -> bool {
-        let pgrep = std::process::Command::new("pgrep")
-            .args(["-f", "emanager daemon"])
-            .output();
-        pgrep.is_ok_and(|output| {
-            String::from_utf8(output.stdout).is_ok_and(|stdout| stdout.lines().count() > 1)
-        })
+    fn wait_stopped() -> anyhow::Result<bool> {
+        for _ in 0..50 {
+            if Lock::owner(LOCK_NAME)?.is_none() {
+                return Ok(true);
+            }
+            std::thread::sleep(Duration::from_millis(100));
+        }
+        Ok(false)
     }
 }
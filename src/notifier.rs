@@ -2,7 +2,29 @@ use crate::{
     config::{CompositorType, CurrentState},
     logger::Logger,
 };
-use notify_rust::{Hint, Notification, Urgency};
+use notify_rust::{Hint, Notification, NotificationHandle, Urgency};
+use serde::{Deserialize, Serialize};
+
+/// Urgency of a notification, as written in the configuration
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, Default)]
+pub enum UrgencyLevel {
+    Low,
+    #[default]
+    Normal,
+    Critical,
+}
+
+impl From<UrgencyLevel> for Urgency {
+    fn from(level: UrgencyLevel) -> Self {
+        match level {
+            UrgencyLevel::Low => Urgency::Low,
+            UrgencyLevel::Normal => Urgency::Normal,
+            UrgencyLevel::Critical => Urgency::Critical,
+        }
+    }
+}
+
+const ACTION: &str = "default";
 
 pub struct Notifier {
     logger: Logger<u32>,
@@ -22,27 +44,123 @@ impl Notifier {
         urgency: Option<Urgency>,
         value: Option<u32>,
     ) -> anyhow::Result<()> {
-        if CompositorType::is_a_compositor_running() {
-            let current_state: CurrentState = CompositorType::get_current_state()?;
-            let color = format!("#{}ee", current_state.color);
-            let mut notif = Notification::new()
-                .summary(summary)
-                .body(body)
-                .hint(Hint::Urgency(urgency.unwrap_or(Urgency::Normal)))
-                .hint(Hint::Custom("frcolor".to_string(), color))
-                .finalize();
-            if let Some(value) = value {
-                notif = notif
-                    .hint(Hint::CustomInt("value".to_string(), value as i32))
-                    .finalize();
+        if let Some(notif) = self.build(summary, body, urgency, value)? {
+            self.show(notif)?;
+        }
+        Ok(())
+    }
+
+    /// Sends a notification with a `label` button, `on_action` being called from another thread
+    /// if it is clicked
+    pub fn send_with_action(
+        &self,
+        summary: &str,
+        body: &str,
+        urgency: Option<Urgency>,
+        label: &str,
+        on_action: impl FnOnce() + Send + 'static,
+    ) -> anyhow::Result<()> {
+        self.send_with_actions(summary, body, urgency, &[(ACTION, label)], |action| {
+            if action == ACTION {
+                on_action()
             }
-            let id = if let Ok(id) = self.logger.read() {
-                notif.id(id).show()?.id()
-            } else {
-                notif.show()?.id()
-            };
-            self.logger.write(&id)?;
+        })
+    }
+
+    /// Sends a notification with buttons given as `(identifier, label)`, `on_action` being called
+    /// from another thread with the identifier of the clicked one, or `__closed` if dismissed
+    pub fn send_with_actions(
+        &self,
+        summary: &str,
+        body: &str,
+        urgency: Option<Urgency>,
+        actions: &[(&str, &str)],
+        on_action: impl FnOnce(&str) + Send + 'static,
+    ) -> anyhow::Result<()> {
+        if let Some(notif) = self.build_with_actions(summary, body, urgency, actions)? {
+            let handle = self.show(notif)?;
+            std::thread::spawn(move || handle.wait_for_action(on_action));
         }
         Ok(())
     }
+
+    /// Changes the text of the notification sent by `send_with_action`, its button still calling
+    /// the `on_action` given then
+    pub fn update_with_action(
+        &self,
+        summary: &str,
+        body: &str,
+        urgency: Option<Urgency>,
+        label: &str,
+    ) -> anyhow::Result<()> {
+        self.update_with_actions(summary, body, urgency, &[(ACTION, label)])
+    }
+
+    /// Changes the text of the notification sent by `send_with_actions`, its buttons still calling
+    /// the `on_action` given then
+    pub fn update_with_actions(
+        &self,
+        summary: &str,
+        body: &str,
+        urgency: Option<Urgency>,
+        actions: &[(&str, &str)],
+    ) -> anyhow::Result<()> {
+        if let Some(notif) = self.build_with_actions(summary, body, urgency, actions)? {
+            self.show(notif)?;
+        }
+        Ok(())
+    }
+
+    fn build_with_actions(
+        &self,
+        summary: &str,
+        body: &str,
+        urgency: Option<Urgency>,
+        actions: &[(&str, &str)],
+    ) -> anyhow::Result<Option<Notification>> {
+        Ok(self.build(summary, body, urgency, None)?.map(|mut notif| {
+            for (identifier, label) in actions {
+                notif.action(identifier, label);
+            }
+            notif
+        }))
+    }
+
+    /// Returns the notification to show, `None` if no compositor is running
+    fn build(
+        &self,
+        summary: &str,
+        body: &str,
+        urgency: Option<Urgency>,
+        value: Option<u32>,
+    ) -> anyhow::Result<Option<Notification>> {
+        if !CompositorType::is_a_compositor_running() {
+            return Ok(None);
+        }
+        let current_state: CurrentState = CompositorType::get_current_state()?;
+        let color = format!("#{}ee", current_state.color);
+        let mut notif = Notification::new()
+            .summary(summary)
+            .body(body)
+            .hint(Hint::Urgency(urgency.unwrap_or(Urgency::Normal)))
+            .hint(Hint::Custom("frcolor".to_string(), color))
+            .finalize();
+        if let Some(value) = value {
+            notif = notif
+                .hint(Hint::CustomInt("value".to_string(), value as i32))
+                .finalize();
+        }
+        Ok(Some(notif))
+    }
+
+    /// Shows `notif` in place of the previous notification of the same name
+    fn show(&self, mut notif: Notification) -> anyhow::Result<NotificationHandle> {
+        let handle = if let Ok(id) = self.logger.read() {
+            notif.id(id).show()?
+        } else {
+            notif.show()?
+        };
+        self.logger.write(&handle.id())?;
+        Ok(handle)
+    }
 }
@@ -0,0 +1,102 @@
+use crate::args::Command;
+use crate::config::Config;
+use crate::daemon::{Daemon, DaemonOp};
+use crate::manager::Manager;
+use crate::utils::runtime_dir;
+use serde::{Deserialize, Serialize};
+use std::io::{BufRead, BufReader, ErrorKind, Write};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::PathBuf;
+
+const SOCKET: &str = "emanager.sock";
+
+pub struct Socket;
+
+impl Socket {
+    /// Listens for commands sent by `emanager` clients and handles them inside the daemon
+    pub fn listen(config: &Config, daemon: &Daemon) -> anyhow::Result<()> {
+        let path = Self::path()?;
+        if path.try_exists()? {
+            std::fs::remove_file(&path)?;
+        }
+        let listener = UnixListener::bind(&path)?;
+
+        std::thread::scope(|scope| {
+            for stream in listener.incoming() {
+                let stream = stream?;
+                scope.spawn(|| Self::respond(stream, config, daemon));
+            }
+            Err(anyhow::anyhow!("Control socket closed"))
+        })
+    }
+
+    /// Sends `command` to the running daemon.
+    /// Returns `None` when no daemon is listening, so the caller can handle it by itself.
+    pub fn send(command: &Command) -> anyhow::Result<Option<Reply>> {
+        let mut stream = match UnixStream::connect(Self::path()?) {
+            Ok(stream) => stream,
+            Err(e) if matches!(e.kind(), ErrorKind::NotFound | ErrorKind::ConnectionRefused) => {
+                return Ok(None)
+            }
+            Err(e) => return Err(e.into()),
+        };
+        stream.write_all(&serde_json::to_vec(command)?)?;
+        stream.write_all(b"\n")?;
+
+        let mut line = String::new();
+        BufReader::new(stream).read_line(&mut line)?;
+        Ok(Some(serde_json::from_str(&line)?))
+    }
+
+    fn respond(stream: UnixStream, config: &Config, daemon: &Daemon) -> anyhow::Result<()> {
+        let mut line = String::new();
+        BufReader::new(&stream).read_line(&mut line)?;
+        let command = serde_json::from_str::<Command>(&line);
+        let reply = match command.as_ref() {
+            Ok(Command::Daemon {
+                operation: Some(DaemonOp::Status),
+                ..
+            }) => Reply::Output(daemon.status().to_string()),
+            Ok(Command::Daemon {
+                operation: Some(DaemonOp::Stop),
+                ..
+            }) => Reply::Ok,
+            Ok(command) => Manager::handle(command.clone(), config).into(),
+            Err(e) => Reply::Err(e.to_string()),
+        };
+        let mut stream = stream;
+        stream.write_all(&serde_json::to_vec(&reply)?)?;
+        stream.write_all(b"\n")?;
+
+        if let Ok(Command::Daemon {
+            operation: Some(DaemonOp::Stop),
+            ..
+        }) = command
+        {
+            std::fs::remove_file(Self::path()?)?;
+            std::process::exit(0);
+        }
+        Ok(())
+    }
+
+    fn path() -> anyhow::Result<PathBuf> {
+        Ok(runtime_dir()?.join(SOCKET))
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub enum Reply {
+    Ok,
+    Output(String),
+    Err(String),
+}
+
+impl From<anyhow::Result<Option<String>>> for Reply {
+    fn from(value: anyhow::Result<Option<String>>) -> Self {
+        match value {
+            Ok(None) => Self::Ok,
+            Ok(Some(output)) => Self::Output(output),
+            Err(e) => Self::Err(e.to_string()),
+        }
+    }
+}
@@ -1,5 +1,94 @@
-pub fn utf8_to_u32(utf8: Vec<u8>) -> anyhow::Result<u32> {
-    let string = String::from_utf8(utf8)?;
-    let value = string[0..string.len() - 1].trim().parse()?;
-    Ok(value)
+use std::path::PathBuf;
+use std::time::Duration;
+
+const RUNTIME_DIR: &str = "emanager";
+
+/// Returns `$XDG_RUNTIME_DIR/emanager`, creating it if needed
+pub fn runtime_dir() -> anyhow::Result<PathBuf> {
+    let mut dir =
+        dirs::runtime_dir().ok_or(anyhow::anyhow!("Could not get your runtime directory"))?;
+    dir.push(RUNTIME_DIR);
+    std::fs::create_dir_all(&dir)?;
+    Ok(dir)
+}
+
+/// Formats a duration as `1h 02m 03s`, omitting leading zero units
+pub fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    let (hours, minutes, seconds) = (secs / 3600, secs / 60 % 60, secs % 60);
+    if hours > 0 {
+        format!("{hours}h {minutes:02}m {seconds:02}s")
+    } else if minutes > 0 {
+        format!("{minutes}m {seconds:02}s")
+    } else {
+        format!("{seconds}s")
+    }
+}
+
+/// Parses a duration like `90`, `45s`, `5m` or `1h30m`, bare numbers being seconds
+pub fn parse_duration(string: &str) -> anyhow::Result<Duration> {
+    let invalid = || anyhow::anyhow!("Invalid duration '{string}', expected something like 1h30m");
+    if string.trim().is_empty() {
+        return Err(invalid());
+    }
+    let (mut secs, mut number) = (0, String::new());
+    for c in string.trim().chars() {
+        let unit = match c {
+            '0'..='9' => {
+                number.push(c);
+                continue;
+            }
+            'h' => 3600,
+            'm' => 60,
+            's' => 1,
+            ' ' => continue,
+            _ => return Err(invalid()),
+        };
+        secs = number
+            .parse::<u64>()
+            .ok()
+            .and_then(|number| number.checked_mul(unit))
+            .and_then(|number| number.checked_add(secs))
+            .ok_or_else(invalid)?;
+        number.clear();
+    }
+    if !number.is_empty() {
+        secs = number
+            .parse::<u64>()
+            .ok()
+            .and_then(|number| number.checked_add(secs))
+            .ok_or_else(invalid)?;
+    }
+    Ok(Duration::from_secs(secs))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_duration_units() {
+        assert_eq!(parse_duration("90").unwrap(), Duration::from_secs(90));
+        assert_eq!(parse_duration("45s").unwrap(), Duration::from_secs(45));
+        assert_eq!(parse_duration("5m").unwrap(), Duration::from_secs(300));
+        assert_eq!(parse_duration("1h30m").unwrap(), Duration::from_secs(5400));
+        assert_eq!(
+            parse_duration("1h 30m 5").unwrap(),
+            Duration::from_secs(5405)
+        );
+    }
+
+    #[test]
+    fn parse_duration_rejects_invalid() {
+        for string in ["", " ", "h", "1x", "1h m", "-5s"] {
+            assert!(parse_duration(string).is_err(), "'{string}' was accepted");
+        }
+    }
+
+    #[test]
+    fn parse_duration_rejects_overflows() {
+        assert!(parse_duration("9999999999999999h").is_err());
+        assert!(parse_duration("99999999999999999999").is_err());
+        assert!(parse_duration(&format!("{}s1", u64::MAX)).is_err());
+    }
 }